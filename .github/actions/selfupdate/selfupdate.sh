@@ -1,6 +1,6 @@
 #!/bin/sh
 set -e
-cargo run --release ghdeps.toml ghversions.toml
+cargo run --release -- ghdeps.toml --output ghversions.toml
 cd .github/actions/selfupdate
 cargo run --release ../../../ghdeps.toml ../../../ghversions.toml ../../../Cargo.toml ./Cargo.toml
 cd ../../..
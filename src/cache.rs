@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use serde_json::json;
+use serde_json::Value;
+
+/// a response body as cached on disk, with the validators needed to
+/// revalidate it with a conditional request
+#[derive(Debug, PartialEq)]
+pub struct CacheEntry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub link: Option<String>,
+    pub body: String
+}
+impl CacheEntry {
+    fn to_json(&self, url: &str) -> String {
+        json!({
+            "url": url,
+            "etag": self.etag,
+            "last_modified": self.last_modified,
+            "link": self.link,
+            "body": self.body
+        }).to_string()
+    }
+    fn from_json(json: &str) -> Option<Self> {
+        let v : Value = serde_json::from_str(json).ok()?;
+        let get_str = |key: &str| v.get(key)
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        Some(Self {
+            etag: get_str("etag"),
+            last_modified: get_str("last_modified"),
+            link: get_str("link"),
+            body: get_str("body")?
+        })
+    }
+}
+pub struct Cache {
+    dir: PathBuf
+}
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+    /// the default location of the cache, following the XDG base directory spec
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var("XDG_CACHE_HOME").ok()
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var("HOME").ok().map(|h| PathBuf::from(h).join(".cache")))
+            .map(|d| d.join("ghdepup"))
+    }
+    /// escapes everything but alphanumerics, '-' and '.', so that distinct
+    /// urls never share a file
+    fn file_name_for(url: &str) -> String {
+        url.bytes()
+            .map(|b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' => (b as char).to_string(),
+                _ => format!("_{:02x}", b)
+            })
+            .collect::<String>() + ".json"
+    }
+    pub async fn get(&self, url: &str) -> Option<CacheEntry> {
+        let content = tokio::fs::read_to_string(self.dir.join(Self::file_name_for(url)))
+            .await
+            .ok()?;
+        CacheEntry::from_json(content.as_str())
+    }
+    pub async fn put(&self, url: &str, entry: &CacheEntry) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.dir.join(Self::file_name_for(url)), entry.to_json(url)).await
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[tokio::test]
+    async fn test_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("ghdepup-cache-test-{}", std::process::id()));
+        let cache = Cache::new(dir.clone());
+        let url = "https://api.github.com/repos/hyperium/hyper/tags?per_page=100&page=1";
+        assert_eq!(cache.get(url).await, None);
+        let entry = CacheEntry {
+            etag: Some("W/\"abc\"".to_string()),
+            last_modified: None,
+            link: None,
+            body: "[]".to_string()
+        };
+        cache.put(url, &entry).await.expect("should write");
+        assert_eq!(cache.get(url).await, Some(entry));
+        assert_eq!(cache.get("https://api.github.com/repos/hyperium/hyper/tags?per_page=100&page=2").await, None);
+        assert_ne!(Cache::file_name_for("a/b"), Cache::file_name_for("a_b"));
+        std::fs::remove_dir_all(dir).expect("should clean up");
+    }
+}
@@ -0,0 +1,79 @@
+/// seconds in a day, as used for the minimum age of versions
+pub const SECS_PER_DAY : u64 = 24 * 60 * 60;
+
+/// days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+/// the inverse of days_from_civil
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+/// parses a RFC 3339 timestamp like `2024-01-02T03:04:05Z` as used by the
+/// GitHub APIs into seconds since the unix epoch
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.split_once(['T', 't', ' '])?;
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+    let (time, offset_secs) = match time.find(['Z', 'z', '+', '-']) {
+        None => return None,
+        Some(pos) => {
+            let (time, offset) = time.split_at(pos);
+            let offset_secs = match offset.split_at(1) {
+                ("Z" | "z", "") => 0,
+                (sign, hours_minutes) => {
+                    let (hours, minutes) = hours_minutes.split_once(':')?;
+                    let secs = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+                    if sign == "-" { -secs } else { secs }
+                }
+            };
+            (time, offset_secs)
+        }
+    };
+    let mut time_parts = time.splitn(3, ':');
+    let hours = time_parts.next()?.parse::<i64>().ok()?;
+    let minutes = time_parts.next()?.parse::<i64>().ok()?;
+    // fractional seconds do not matter for an age in days
+    let seconds = time_parts.next()?.split('.').next()?.parse::<i64>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day) * SECS_PER_DAY as i64
+        + hours * 3600 + minutes * 60 + seconds
+        - offset_secs;
+    u64::try_from(secs).ok()
+}
+/// formats seconds since the unix epoch as a date like `2024-01-02`
+pub fn format_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / SECS_PER_DAY) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[tokio::test]
+    async fn test_parse_and_format_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2024-02-29T12:30:15Z"), Some(1709209815));
+        assert_eq!(parse_timestamp("2024-02-29T14:30:15.123+02:00"), Some(1709209815));
+        assert_eq!(parse_timestamp("2024-02-29"), None);
+        assert_eq!(parse_timestamp("2024-13-01T00:00:00Z"), None);
+        assert_eq!(format_date(1709209815), "2024-02-29");
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(parse_timestamp("2000-03-01T00:00:00Z").unwrap()), "2000-03-01");
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use itertools::Itertools;
+use serde_json::json;
+use serde_json::Value;
+
+use super::Dep;
+use super::GetTagsError;
+use super::GhClient;
+use super::TagEntry;
+use super::TagSource;
+use super::date;
+use super::get_tag_name;
+use super::gh_request_builder;
+use super::send_request;
+
+/// repositories asked for in one query
+const BATCH_SIZE : usize = 20;
+const PER_PAGE : u64 = 100;
+
+/// the GraphQL endpoint belonging to a REST API base url, on GitHub
+/// Enterprise Server the REST API lives under /api/v3 and GraphQL under
+/// /api/graphql
+pub fn graphql_url(api_url: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    match api_url.strip_suffix("/api/v3") {
+        Some(base) => format!("{}/api/graphql", base),
+        None => format!("{}/graphql", api_url)
+    }
+}
+fn quote(s: &str) -> String {
+    // JSON strings are valid GraphQL string literals
+    Value::from(s).to_string()
+}
+fn connection_name(source: &TagSource) -> &'static str {
+    match source {
+        TagSource::Tags => "refs",
+        TagSource::Releases => "releases"
+    }
+}
+fn build_repo_query(alias: &str, dep: &Dep, cursor: Option<&str>) -> String {
+    let (owner, name) = dep.project.split_once('/').unwrap_or((dep.project.as_str(), ""));
+    let after = cursor.map(quote).unwrap_or("null".to_string());
+    // refs are sorted by name unless asked otherwise, paging newest first
+    // relies on the newest tags coming first as with the REST API
+    let connection = match dep.source {
+        TagSource::Tags => format!("refs(refPrefix: \"refs/tags/\", first: {}, after: {}, orderBy: {{field: TAG_COMMIT_DATE, direction: DESC}})", PER_PAGE, after),
+        TagSource::Releases => format!("releases(first: {}, after: {}, orderBy: {{field: CREATED_AT, direction: DESC}})", PER_PAGE, after)
+    };
+    let nodes = match dep.source {
+        // annotated tags point to a tag object, which points to the commit
+        TagSource::Tags => "name target { oid ... on Commit { committedDate } ... on Tag { target { oid ... on Commit { committedDate } } } }",
+        TagSource::Releases => "tagName isDraft isPrerelease publishedAt tagCommit { oid }"
+    };
+    format!("{}: repository(owner: {}, name: {}) {{ {} {{ totalCount pageInfo {{ hasNextPage endCursor }} nodes {{ {} }} }} }}",
+        alias,
+        quote(owner),
+        quote(name),
+        connection,
+        nodes)
+}
+/// one aliased query for all the given deps, with the alias r<index>
+pub fn build_query(deps: &[(usize, &Dep, Option<&str>)]) -> String {
+    let repos = deps.iter()
+        .map(|(index, dep, cursor)| build_repo_query(format!("r{}", index).as_str(), dep, *cursor))
+        .join(" ");
+    format!("query {{ {} }}", repos)
+}
+/// a page of tags for one repository from a query response
+#[derive(Debug, PartialEq)]
+pub struct RepoPage {
+    pub tags: Vec<TagEntry>,
+    pub total_count: Option<u64>,
+    pub end_cursor: Option<String>
+}
+fn get_error_message(response: &Value, alias: &str) -> Option<String> {
+    let errors = response.get("errors")?.as_array()?;
+    errors.iter()
+        .find(|e| {
+            e.get("path")
+                .and_then(|p| p.get(0))
+                .and_then(Value::as_str) == Some(alias)
+        })
+        .or(errors.first())?
+        .get("message")?
+        .as_str()
+        .map(str::to_owned)
+}
+pub fn parse_repo_page(response: &Value, alias: &str, dep: &Dep) -> Result<RepoPage, GetTagsError> {
+    let connection = response.get("data")
+        .and_then(|d| d.get(alias))
+        .filter(|r| !r.is_null())
+        .ok_or_else(|| GetTagsError::GraphqlError(
+            get_error_message(response, alias).unwrap_or(format!("repository {} not found", dep.project))))?
+        .get(connection_name(&dep.source))
+        .ok_or(GetTagsError::ExpectedJsonObjectError())?;
+    let nodes = connection.get("nodes")
+        .and_then(Value::as_array)
+        .ok_or(GetTagsError::ExpectedJsonArrayError())?;
+    let is_set = |node: &Value, flag: &str| node.get(flag)
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let get_str = |node: Option<&Value>, key: &str| node
+        .and_then(|n| n.get(key))
+        .and_then(Value::as_str)
+        .map(str::to_owned);
+    let get_date = |node: Option<&Value>, key: &str| get_str(node, key)
+        .and_then(|d| date::parse_timestamp(d.as_str()));
+    let tags = match dep.source {
+        TagSource::Tags => nodes.iter()
+            .filter_map(|n| {
+                let target = n.get("target");
+                let tag_target = target.and_then(|t| t.get("target"));
+                let commit = get_str(tag_target, "oid").or(get_str(target, "oid"));
+                let date = get_date(target, "committedDate")
+                    .or(get_date(tag_target, "committedDate"));
+                get_tag_name(n, "name").ok().map(|name| TagEntry { name: name.to_owned(), commit, date, tarball_url: None })
+            })
+            .collect_vec(),
+        TagSource::Releases => nodes.iter()
+            .filter(|&n| !is_set(n, "isDraft"))
+            .filter(|&n| dep.include_prereleases || !is_set(n, "isPrerelease"))
+            .filter_map(|n| {
+                let commit = get_str(n.get("tagCommit"), "oid");
+                let date = get_date(Some(n), "publishedAt");
+                get_tag_name(n, "tagName").ok().map(|name| TagEntry { name: name.to_owned(), commit, date, tarball_url: None })
+            })
+            .collect_vec()
+    };
+    let page_info = connection.get("pageInfo");
+    let has_next_page = page_info
+        .and_then(|p| p.get("hasNextPage"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let end_cursor = page_info
+        .and_then(|p| p.get("endCursor"))
+        .and_then(Value::as_str)
+        .filter(|_| has_next_page)
+        .map(str::to_owned);
+    Ok(RepoPage {
+        tags,
+        total_count: connection.get("totalCount").and_then(Value::as_u64),
+        end_cursor
+    })
+}
+async fn post_query(gh: &GhClient, url: &str, query: String) -> Result<Value, GetTagsError> {
+    let body = json!({ "query": query }).to_string();
+    let (_, _, buf) = send_request(gh, url, || {
+        gh_request_builder(gh, url)
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .body(hyper::Body::from(body.clone()))
+    }).await?;
+    serde_json::from_slice(&buf)
+        .map_err(|_| GetTagsError::JsonParseError())
+}
+/// fetches the tags of all deps with batched GraphQL queries, paging each
+/// repository separately until it has no next page or wants no more
+pub async fn update_tags_from_graphql(deps: &mut [Dep], gh: &GhClient) -> Vec<Result<(), GetTagsError>> {
+    let mut results = deps.iter().map(|_| Ok(())).collect_vec();
+    let mut by_api_url : BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    deps.iter().enumerate().for_each(|(index, dep)| {
+        let api_url = dep.api_url.clone().unwrap_or(gh.api_url.clone());
+        by_api_url.entry(api_url).or_default().push(index);
+    });
+    for (api_url, indices) in by_api_url {
+        let url = graphql_url(api_url.as_str());
+        for batch in indices.chunks(BATCH_SIZE) {
+            let mut pending : Vec<(usize, Option<String>)> = batch.iter()
+                .map(|&index| (index, None))
+                .collect_vec();
+            pending.iter().for_each(|&(index, _)| deps[index].start_paging());
+            while !pending.is_empty() {
+                let query = build_query(&pending.iter()
+                    .map(|(index, cursor)| (*index, &deps[*index], cursor.as_deref()))
+                    .collect_vec());
+                let response = match post_query(gh, url.as_str(), query).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        pending.iter().for_each(|&(index, _)| {
+                            results[index] = Err(GetTagsError::GraphqlError(format!("{}: {}", deps[index].project, e)));
+                        });
+                        break;
+                    }
+                };
+                pending = pending.into_iter()
+                    .filter_map(|(index, _)| {
+                        let dep = &mut deps[index];
+                        match parse_repo_page(&response, format!("r{}", index).as_str(), dep) {
+                            Err(e) => {
+                                results[index] = Err(e);
+                                None
+                            },
+                            Ok(page) => {
+                                dep.pages_total = page.total_count
+                                    .map(|total| total.div_ceil(PER_PAGE) as u32);
+                                let wants_next_page = dep.add_page_of_tags(page.tags, page.end_cursor.is_some());
+                                page.end_cursor
+                                    .filter(|_| wants_next_page)
+                                    .map(|cursor| (index, Some(cursor)))
+                            }
+                        }
+                    })
+                    .collect_vec();
+            }
+        }
+    }
+    results
+}
+#[cfg(test)]
+mod tests {
+    use toml::Table;
+
+    use super::*;
+    #[tokio::test]
+    async fn test_graphql_query_and_response() {
+        assert_eq!(graphql_url("https://api.github.com"), "https://api.github.com/graphql");
+        assert_eq!(graphql_url("https://ghes.example.com/api/v3/"), "https://ghes.example.com/api/graphql");
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+SERDE_GH_PROJECT=\"serde-rs/serde\"
+SERDE_GH_SOURCE=\"releases\"
+        ").expect("should parse");
+        let hyper = Dep::from_table(&config, "hyper");
+        let serde = Dep::from_table(&config, "serde");
+        let query = build_query(&[(0, &hyper, None), (1, &serde, Some("Y3Vyc29y"))]);
+        assert_eq!(query, "query { \
+            r0: repository(owner: \"hyperium\", name: \"hyper\") { refs(refPrefix: \"refs/tags/\", first: 100, after: null, orderBy: {field: TAG_COMMIT_DATE, direction: DESC}) { totalCount pageInfo { hasNextPage endCursor } nodes { name target { oid ... on Commit { committedDate } ... on Tag { target { oid ... on Commit { committedDate } } } } } } } \
+            r1: repository(owner: \"serde-rs\", name: \"serde\") { releases(first: 100, after: \"Y3Vyc29y\", orderBy: {field: CREATED_AT, direction: DESC}) { totalCount pageInfo { hasNextPage endCursor } nodes { tagName isDraft isPrerelease publishedAt tagCommit { oid } } } } }");
+        let response : Value = serde_json::from_str("{
+            \"data\": {
+                \"r0\": { \"refs\": {
+                    \"totalCount\": 101,
+                    \"pageInfo\": { \"hasNextPage\": true, \"endCursor\": \"MTAw\" },
+                    \"nodes\": [
+                        { \"name\": \"v1.0.0\", \"target\": { \"oid\": \"c0\", \"committedDate\": \"2024-01-02T03:04:05Z\" } },
+                        { \"name\": \"v1.0.1\", \"target\": { \"oid\": \"t1\", \"target\": { \"oid\": \"c1\", \"committedDate\": \"2024-01-03T03:04:05Z\" } } } ] } },
+                \"r1\": { \"releases\": {
+                    \"totalCount\": 3,
+                    \"pageInfo\": { \"hasNextPage\": false, \"endCursor\": \"Mw\" },
+                    \"nodes\": [
+                        { \"tagName\": \"v2.0.0\", \"isDraft\": true, \"isPrerelease\": false },
+                        { \"tagName\": \"v1.1.0-rc.1\", \"isDraft\": false, \"isPrerelease\": true },
+                        { \"tagName\": \"v1.0.0\", \"isDraft\": false, \"isPrerelease\": false, \"publishedAt\": null, \"tagCommit\": { \"oid\": \"c2\" } } ] } },
+                \"r2\": null
+            },
+            \"errors\": [ { \"path\": [\"r2\"], \"message\": \"Could not resolve to a Repository with the name 'o/gone'.\" } ]
+        }").expect("should parse");
+        assert_eq!(parse_repo_page(&response, "r0", &hyper).expect("should parse"), RepoPage {
+            tags: vec![
+                TagEntry { name: "v1.0.0".to_string(), commit: Some("c0".to_string()), date: Some(1704164645), tarball_url: None },
+                TagEntry { name: "v1.0.1".to_string(), commit: Some("c1".to_string()), date: Some(1704251045), tarball_url: None }],
+            total_count: Some(101),
+            end_cursor: Some("MTAw".to_string())
+        });
+        assert_eq!(parse_repo_page(&response, "r1", &serde).expect("should parse"), RepoPage {
+            tags: vec![TagEntry { name: "v1.0.0".to_string(), commit: Some("c2".to_string()), date: None, tarball_url: None }],
+            total_count: Some(3),
+            end_cursor: None
+        });
+        assert_eq!(
+            parse_repo_page(&response, "r2", &hyper).err().map(|e| e.to_string()),
+            Some("graphql error: Could not resolve to a Repository with the name 'o/gone'.".to_string()));
+    }
+}
@@ -0,0 +1,151 @@
+use std::str::FromStr;
+use itertools::Itertools;
+use semver::VersionReq;
+use toml::Table;
+use toml::Value;
+
+use super::Dep;
+use super::DepKey;
+use super::GhDepContainer;
+use super::IgnoredVersion;
+use super::NoMatchPolicy;
+use super::OutputKey;
+use super::UpdatePolicy;
+
+/// the values accepted by keys that select one of a few behaviours, a run
+/// silently falls back to the default for anything else
+fn allowed_values(key: &DepKey) -> Option<&'static [&'static str]> {
+    match key {
+        DepKey::AllowDowngrade | DepKey::IncludePrereleases => Some(&["true", "false"]),
+        DepKey::Paging => Some(&["all", "newest-first"]),
+        DepKey::Prerelease => Some(&["never", "allow", "only-if-current-is-prerelease"]),
+        DepKey::Source => Some(&["tags", "releases"]),
+        DepKey::VersionParse => Some(&["strict", "lenient"]),
+        _ => None
+    }
+}
+/// checks a single value, returning what is wrong with it
+fn check_value(key: &DepKey, value: &str) -> Option<String> {
+    if let Some(allowed) = allowed_values(key) {
+        return match allowed.contains(&value) {
+            true => None,
+            false => Some(format!("expected one of {}", allowed.join(", ")))
+        };
+    }
+    match key {
+        DepKey::MaxPages | DepKey::MinAgeDays => value.parse::<u32>().err()
+            .map(|_| "expected a number of days or pages".to_string()),
+        DepKey::OnNoMatch => NoMatchPolicy::from_str(value).err()
+            .map(|_| "expected one of keep, fail".to_string()),
+        DepKey::UpdatePolicy => UpdatePolicy::from_str(value).err()
+            .map(|_| "expected one of patch, minor, major".to_string()),
+        DepKey::OutputKeys => OutputKey::parse_list(value).err()
+            .map(|_| "expected a list of version, tag, commit, sha256".to_string()),
+        DepKey::VersionReq => VersionReq::parse(value).err()
+            .map(|e| format!("not a version requirement: {}", e)),
+        DepKey::IgnoreVersions => {
+            let invalid = IgnoredVersion::split_list(value).into_iter()
+                .filter(|entry| IgnoredVersion::parse_entry(entry).is_none())
+                .collect_vec();
+            match invalid.is_empty() {
+                true => None,
+                false => Some(format!("not a version or range: {}", invalid.join(", ")))
+            }
+        },
+        _ => None
+    }
+}
+/// splits a key like `HYPER_TLS_GH_VERSION_REQ` into the dependency and the
+/// key, the longest known key wins as e.g. `TAG` is a suffix of `GH_TAG`
+fn split_key(key: &str) -> Option<(String, DepKey)> {
+    DepKey::ALL.into_iter()
+        .filter(|dep_key| key.ends_with(format!("_{}", dep_key.as_full_postfix()).as_str()))
+        .max_by_key(|dep_key| dep_key.as_str().len())
+        .map(|dep_key| {
+            let dep = &key[..key.len() - dep_key.as_full_postfix().len() - 1];
+            (dep.to_ascii_lowercase(), dep_key)
+        })
+}
+/// finds the problems in a config that a run would silently ignore or only
+/// report once it fails, without asking GitHub
+pub fn lint_config(config: &Table) -> Vec<String> {
+    let deps = config.get_all_deps();
+    let mut problems = vec![];
+    if deps.is_empty() {
+        problems.push("no dependency declared, each needs a _GH_PROJECT key".to_string());
+    }
+    config.iter().for_each(|(key, value)| {
+        // other tools may keep their own keys in the same files
+        if !key.contains("_GH_") {
+            return;
+        }
+        let Some((dep, dep_key)) = split_key(key) else {
+            problems.push(format!("{}: unknown key", key));
+            return;
+        };
+        if !deps.contains(&dep) {
+            problems.push(format!("{}: no {}_GH_PROJECT declared", key, dep.to_ascii_uppercase()));
+        }
+        match value {
+            Value::String(value) => if let Some(problem) = check_value(&dep_key, value.as_str()) {
+                problems.push(format!("{}: invalid value \"{}\", {}", key, value, problem));
+            },
+            _ => problems.push(format!("{}: values have to be double quoted strings", key))
+        }
+    });
+    deps.iter().for_each(|name| {
+        let dep = Dep::from_table(config, name);
+        if let Some(e) = &dep.error {
+            problems.push(format!("{}: {}", name, e));
+        }
+        let version = config.get_ghdep_info(name, DepKey::Version).unwrap_or_default();
+        if !version.is_empty() && dep.current_version.is_none() {
+            problems.push(format!("{}: current version \"{}\" can't be parsed", name, version));
+        }
+    });
+    problems
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[tokio::test]
+    async fn test_lint_config() {
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+HYPER_GH_TAG_PREFIX=\"v\"
+HYPER_GH_VERSION_REQ=\">=0.14, <1\"
+HYPER_GH_VERSION=\"0.14.26\"
+HYPER_CRATE_NAME=\"hyper\"
+HYPER_TLS_GH_PROJECT=\"hyperium/hyper-tls\"
+HYPER_TLS_GH_TAG_PATTERN=\"v(?P<version>.*)\"
+HYPER_TLS_GH_IGNORE_VERSIONS=\"0.5.1, latest\"")
+            .expect("should parse");
+        assert_eq!(lint_config(&config), vec![
+            "HYPER_TLS_GH_IGNORE_VERSIONS: invalid value \"0.5.1, latest\", not a version or range: latest".to_string()
+        ]);
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+HYPER_GH_IGNORE_VERSIONS=\"0.14.27, 1.4.*, >=1.5, <1.6, 0.6.0; 0.7.0\"")
+            .expect("should parse");
+        assert_eq!(lint_config(&config), vec![
+            "HYPER_GH_IGNORE_VERSIONS: invalid value \"0.14.27, 1.4.*, >=1.5, <1.6, 0.6.0; 0.7.0\", not a version or range: 0.6.0; 0.7.0".to_string()
+        ]);
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+HYPER_GH_SOURCE=\"release\"
+HYPER_GH_MAX_PAGES=3
+HYPER_GH_VERSION=\"v0.14\"
+HYPER_GH_TAG_PATTERN=\"v(.*)\"
+HYPER_GH_TAG_PREFIXX=\"v\"
+TOKIO_GH_VERSION=\"1.0.0\"")
+            .expect("should parse");
+        assert_eq!(lint_config(&config), vec![
+            "HYPER_GH_MAX_PAGES: values have to be double quoted strings".to_string(),
+            "HYPER_GH_SOURCE: invalid value \"release\", expected one of tags, releases".to_string(),
+            "HYPER_GH_TAG_PREFIXX: unknown key".to_string(),
+            "TOKIO_GH_VERSION: no TOKIO_GH_PROJECT declared".to_string(),
+            "hyper: tag pattern \"v(.*)\" has no group named version".to_string(),
+            "hyper: current version \"v0.14\" can't be parsed".to_string()
+        ]);
+    }
+}
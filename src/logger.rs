@@ -0,0 +1,45 @@
+use log::Level;
+use log::LevelFilter;
+use log::Log;
+use log::Metadata;
+use log::Record;
+
+/// writes the log of ghdepup to stderr. Info records are the summary of a
+/// run and written as they are, all others get their level as prefix, so
+/// that e.g. `grep ^debug:` finds the details.
+struct StderrLogger;
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // dependencies may log as well, but only our own records are of use
+        metadata.level() <= log::max_level() && metadata.target().starts_with("ghdepup")
+    }
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Info => eprintln!("{}", record.args()),
+            Level::Warn => eprintln!("warning: {}", record.args()),
+            level => eprintln!("{}: {}", level.as_str().to_ascii_lowercase(), record.args())
+        }
+    }
+    fn flush(&self) {}
+}
+static LOGGER : StderrLogger = StderrLogger;
+
+pub fn init(level: LevelFilter) {
+    // only fails if a logger is already set, which then stays in use
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+/// the level after one more `-v`
+pub fn more_verbose(level: LevelFilter) -> LevelFilter {
+    match level {
+        LevelFilter::Off => LevelFilter::Error,
+        LevelFilter::Error => LevelFilter::Warn,
+        LevelFilter::Warn => LevelFilter::Info,
+        LevelFilter::Info => LevelFilter::Debug,
+        LevelFilter::Debug | LevelFilter::Trace => LevelFilter::Trace
+    }
+}
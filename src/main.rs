@@ -1,39 +1,126 @@
-use futures::future::join_all;
+mod cache;
+mod date;
+mod graphql;
+mod lint;
+mod logger;
+mod report;
+mod snapshot;
+
+use cache::Cache;
+use cache::CacheEntry;
+use futures::stream;
+use futures::StreamExt;
+use snapshot::Snapshot;
 use hyper_tls::HttpsConnector;
 use hyper::Client;
+use hyper::client::HttpConnector;
 use hyper::Request;
+use hyper::body::HttpBody;
 use itertools::Itertools;
+use log::LevelFilter;
+use regex::Regex;
 use semver::VersionReq;
+use sha2::Digest;
+use sha2::Sha256;
 use toml::Table;
+use std::collections::BTreeMap;
 use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::str;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
 use std::str::FromStr;
 use std::string::FromUtf8Error;
 use serde_json::Value;
 use semver::Version;
 
 enum DepKey {
+    AllowDowngrade,
+    ApiUrl,
+    Commit,
+    IgnoreVersions,
+    IncludePrereleases,
+    MaxPages,
+    MinAgeDays,
+    OnNoMatch,
+    OutputKeys,
+    Paging,
+    Prerelease,
     Project,
+    Sha256,
+    Source,
+    Tag,
+    TagPattern,
     TagPrefix,
+    UpdatePolicy,
     Version,
-    VersionReq
+    VersionParse,
+    VersionReq,
+    VersionSeparators
 }
 impl DepKey {
+    const ALL : [DepKey; 22] = [
+        DepKey::AllowDowngrade,
+        DepKey::ApiUrl,
+        DepKey::Commit,
+        DepKey::IgnoreVersions,
+        DepKey::IncludePrereleases,
+        DepKey::MaxPages,
+        DepKey::MinAgeDays,
+        DepKey::OnNoMatch,
+        DepKey::OutputKeys,
+        DepKey::Paging,
+        DepKey::Prerelease,
+        DepKey::Project,
+        DepKey::Sha256,
+        DepKey::Source,
+        DepKey::Tag,
+        DepKey::TagPattern,
+        DepKey::TagPrefix,
+        DepKey::UpdatePolicy,
+        DepKey::Version,
+        DepKey::VersionParse,
+        DepKey::VersionReq,
+        DepKey::VersionSeparators
+    ];
     fn as_str(&self) -> &'static str {
         match self {
+            DepKey::AllowDowngrade => "ALLOW_DOWNGRADE",
+            DepKey::ApiUrl => "API_URL",
+            DepKey::Commit => "COMMIT",
+            DepKey::IgnoreVersions => "IGNORE_VERSIONS",
+            DepKey::IncludePrereleases => "INCLUDE_PRERELEASES",
+            DepKey::MaxPages => "MAX_PAGES",
+            DepKey::MinAgeDays => "MIN_AGE_DAYS",
+            DepKey::OnNoMatch => "ON_NO_MATCH",
+            DepKey::OutputKeys => "OUTPUT_KEYS",
+            DepKey::Paging => "PAGING",
+            DepKey::Prerelease => "PRERELEASE",
             DepKey::Project => "PROJECT",
+            DepKey::Sha256 => "SHA256",
+            DepKey::Source => "SOURCE",
+            DepKey::Tag => "TAG",
+            DepKey::TagPattern => "TAG_PATTERN",
             DepKey::TagPrefix => "TAG_PREFIX",
+            DepKey::UpdatePolicy => "UPDATE_POLICY",
             DepKey::Version => "VERSION",
-            DepKey::VersionReq => "VERSION_REQ"
+            DepKey::VersionParse => "VERSION_PARSE",
+            DepKey::VersionReq => "VERSION_REQ",
+            DepKey::VersionSeparators => "VERSION_SEPARATORS"
         }
     }
     fn as_full_postfix(&self) -> String {
         const GH_PREFIX : &str = "GH";
-        return format!("{}_{}", GH_PREFIX, self.as_str());
+        format!("{}_{}", GH_PREFIX, self.as_str())
     }
 }
 trait GhDepContainer {
     fn get_ghdep_info(&self, dep: &str, key: DepKey) -> Option<String>;
+    fn get_ghdep_flag(&self, dep: &str, key: DepKey) -> bool {
+        self.get_ghdep_info(dep, key).is_some_and(|v| v == "true")
+    }
     fn get_all_deps(&self) -> Vec<String>;
 }
 impl GhDepContainer for Table {
@@ -42,58 +129,518 @@ impl GhDepContainer for Table {
         let value = self
             .get::<String>(&key)?
             .as_str()?;
-        return Some(value.to_string());
+        Some(value.to_string())
     }
     fn get_all_deps(&self) -> Vec<String>{
         let postfix = DepKey::Project.as_full_postfix();
         let chars_to_remove = postfix.len() + 1;
-        return self.keys()
+        self.keys()
             .filter(|&k| { k.ends_with(postfix.as_str()) })
             .map(|k| {
-                return k[..k.len()-chars_to_remove].to_ascii_lowercase()
+                k[..k.len()-chars_to_remove].to_ascii_lowercase()
             })
-            .collect_vec();
+            .collect_vec()
+    }
+}
+#[derive(Debug, PartialEq)]
+enum TagSource {
+    Tags,
+    Releases
+}
+impl TagSource {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("releases") => TagSource::Releases,
+            _ => TagSource::Tags
+        }
+    }
+    fn endpoint(&self) -> &'static str {
+        match self {
+            TagSource::Tags => "tags",
+            TagSource::Releases => "releases"
+        }
+    }
+}
+#[derive(Debug, PartialEq)]
+enum Paging {
+    All,
+    NewestFirst
+}
+impl Paging {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("newest-first") => Paging::NewestFirst,
+            _ => Paging::All
+        }
+    }
+}
+#[derive(Debug, PartialEq)]
+enum NoMatchPolicy {
+    Keep,
+    Fail
+}
+impl FromStr for NoMatchPolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(NoMatchPolicy::Keep),
+            "fail" => Ok(NoMatchPolicy::Fail),
+            _ => Err(())
+        }
+    }
+}
+/// how far a dep may move away from its current version
+#[derive(Debug, PartialEq)]
+enum UpdatePolicy {
+    Patch,
+    Minor,
+    Major
+}
+impl FromStr for UpdatePolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "patch" => Ok(UpdatePolicy::Patch),
+            "minor" => Ok(UpdatePolicy::Minor),
+            "major" => Ok(UpdatePolicy::Major),
+            _ => Err(())
+        }
+    }
+}
+/// how big the step from the current to a new version is, ordered so that
+/// a threshold takes all bigger steps as well
+#[derive(Debug, PartialEq, PartialOrd)]
+enum UpdateKind {
+    Patch,
+    Minor,
+    Major
+}
+impl FromStr for UpdateKind {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "patch" => Ok(UpdateKind::Patch),
+            "minor" => Ok(UpdateKind::Minor),
+            "major" => Ok(UpdateKind::Major),
+            _ => Err(())
+        }
+    }
+}
+impl UpdateKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UpdateKind::Patch => "patch",
+            UpdateKind::Minor => "minor",
+            UpdateKind::Major => "major"
+        }
+    }
+    /// changes of prereleases and build metadata count as patches, a first
+    /// version as major
+    fn between(current: Option<&Version>, new: &Version) -> Self {
+        match current {
+            Some(current) if current.major == new.major && current.minor == new.minor => UpdateKind::Patch,
+            Some(current) if current.major == new.major => UpdateKind::Minor,
+            _ => UpdateKind::Major
+        }
+    }
+}
+impl UpdatePolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UpdatePolicy::Patch => "patch",
+            UpdatePolicy::Minor => "minor",
+            UpdatePolicy::Major => "major"
+        }
+    }
+    fn allows(&self, current: &Version, candidate: &Version) -> bool {
+        match self {
+            UpdatePolicy::Patch => candidate.major == current.major && candidate.minor == current.minor,
+            UpdatePolicy::Minor => candidate.major == current.major,
+            UpdatePolicy::Major => true
+        }
+    }
+}
+#[derive(Debug, PartialEq)]
+enum PrereleasePolicy {
+    Never,
+    Allow,
+    OnlyIfCurrentIsPrerelease
+}
+impl PrereleasePolicy {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("allow") => PrereleasePolicy::Allow,
+            Some("only-if-current-is-prerelease") => PrereleasePolicy::OnlyIfCurrentIsPrerelease,
+            _ => PrereleasePolicy::Never
+        }
+    }
+    fn allows_prereleases(&self, current: Option<&Version>) -> bool {
+        match self {
+            PrereleasePolicy::Never => false,
+            PrereleasePolicy::Allow => true,
+            PrereleasePolicy::OnlyIfCurrentIsPrerelease => current.is_some_and(|v| !v.pre.is_empty())
+        }
+    }
+}
+/// an entry of the ignore list, either an exact version or a range
+#[derive(Debug, PartialEq)]
+enum IgnoredVersion {
+    Exact(Version),
+    Range(VersionReq)
+}
+impl IgnoredVersion {
+    /// parses a comma separated list, each entry being an exact version or
+    /// a range like `1.4.*` or `>=1.5, <1.6`
+    fn parse_list(list: &str) -> Vec<IgnoredVersion> {
+        IgnoredVersion::split_list(list).iter()
+            .filter_map(|entry| IgnoredVersion::parse_entry(entry))
+            .collect_vec()
+    }
+    /// splits a comma separated list into entries, an upper bound like `<1.6`
+    /// right after a lower bound like `>=1.5` belongs to the same range
+    fn split_list(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .fold(Vec::<Vec<&str>>::new(), |mut entries, part| {
+                match entries.last_mut() {
+                    Some(entry) if part.starts_with('<') && entry.last().is_some_and(|last| last.starts_with('>')) => entry.push(part),
+                    _ => entries.push(vec![part])
+                }
+                entries
+            })
+            .iter()
+            .map(|entry| entry.join(", "))
+            .collect_vec()
+    }
+    fn parse_entry(entry: &str) -> Option<IgnoredVersion> {
+        Version::parse(entry).map(IgnoredVersion::Exact)
+            .or(VersionReq::parse(entry).map(IgnoredVersion::Range))
+            .ok()
+    }
+    fn matches(&self, v: &Version) -> bool {
+        match self {
+            IgnoredVersion::Exact(ignored) => ignored == v,
+            // a range also ignores the prereleases leading up to its releases
+            IgnoredVersion::Range(vr) => vr.matches(v) || vr.matches(&Version::new(v.major, v.minor, v.patch))
+        }
+    }
+}
+/// a key written to the output file for each dep besides the version
+#[derive(Clone, Debug, PartialEq)]
+enum OutputKey {
+    Version,
+    Tag,
+    Commit,
+    Sha256
+}
+impl FromStr for OutputKey {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "version" => Ok(OutputKey::Version),
+            "tag" => Ok(OutputKey::Tag),
+            "commit" => Ok(OutputKey::Commit),
+            "sha256" => Ok(OutputKey::Sha256),
+            _ => Err(())
+        }
+    }
+}
+impl OutputKey {
+    /// parses a comma separated list like `tag,commit`
+    fn parse_list(list: &str) -> Result<Vec<OutputKey>, ()> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(OutputKey::from_str)
+            .collect()
+    }
+}
+/// how strictly the version part of a tag has to follow semver
+#[derive(Debug, PartialEq)]
+enum VersionParse {
+    Strict,
+    Lenient
+}
+impl VersionParse {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("lenient") => VersionParse::Lenient,
+            _ => VersionParse::Strict
+        }
+    }
+    /// parses a version, in lenient mode missing minor and patch versions
+    /// are taken as 0 and leading zeros are accepted, e.g. `2024.01` is
+    /// read as `2024.1.0`
+    fn parse(&self, version: &str) -> Option<Version> {
+        if let Ok(v) = Version::parse(version) {
+            return Some(v);
+        }
+        if *self == VersionParse::Strict {
+            return None;
+        }
+        let core_end = version.find(['-', '+']).unwrap_or(version.len());
+        let (core, rest) = version.split_at(core_end);
+        let components = core.split('.')
+            .map(|c| Some(c)
+                .filter(|c| !c.is_empty() && c.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|c| c.parse::<u64>().ok()))
+            .collect::<Option<Vec<u64>>>()?;
+        if components.len() > 3 {
+            return None;
+        }
+        let component = |index: usize| components.get(index).copied().unwrap_or_default();
+        Version::parse(format!("{}.{}.{}{}", component(0), component(1), component(2), rest).as_str()).ok()
+    }
+}
+/// how the version is found in a tag: after a literal prefix or as the
+/// `version` group of a regex matching the whole tag
+struct TagPattern {
+    prefix: Option<String>,
+    regex: Regex,
+    description: String,
+    separators: String,
+    version_parse: VersionParse
+}
+impl TagPattern {
+    fn from_prefix(prefix: &str, separators: &str) -> Self {
+        Self {
+            prefix: Some(prefix.to_string()),
+            regex: Regex::new(format!("^{}(?P<version>.*)$", regex::escape(prefix)).as_str())
+                .expect("an escaped prefix is a valid regex"),
+            description: format!("the prefix \"{}\"", prefix),
+            separators: separators.to_string(),
+            version_parse: VersionParse::Strict
+        }
+    }
+    fn from_regex(pattern: &str, separators: &str) -> Result<Self, String> {
+        let regex = Regex::new(format!("^(?:{})$", pattern).as_str())
+            .map_err(|e| format!("invalid tag pattern \"{}\": {}", pattern, e))?;
+        if !regex.capture_names().any(|name| name == Some("version")) {
+            return Err(format!("tag pattern \"{}\" has no group named version", pattern));
+        }
+        Ok(Self {
+            prefix: None,
+            regex,
+            description: format!("the pattern \"{}\"", pattern),
+            separators: separators.to_string(),
+            version_parse: VersionParse::Strict
+        })
+    }
+    /// the version part of a tag, with the separators replaced by dots
+    fn get_version_str(&self, tag: &str) -> Option<String> {
+        let version = self.regex.captures(tag)?.name("version")?.as_str();
+        Some(version.chars()
+            .map(|c| if self.separators.contains(c) { '.' } else { c })
+            .collect())
+    }
+    fn get_version(&self, tag: &str) -> Option<Version> {
+        self.version_parse.parse(self.get_version_str(tag)?.as_str())
+    }
+    /// the tag of a version, if it can be told from the version alone
+    fn format_tag(&self, version: &Version) -> Option<String> {
+        if !self.separators.is_empty() || self.version_parse != VersionParse::Strict {
+            return None;
+        }
+        self.prefix.as_ref().map(|prefix| format!("{}{}", prefix, version))
+    }
+}
+/// a tag as listed by GitHub, with the commit it points to, the time it
+/// was published and the url of its tarball if known
+#[derive(Debug, PartialEq)]
+struct TagEntry {
+    name: String,
+    commit: Option<String>,
+    date: Option<u64>,
+    tarball_url: Option<String>
+}
+/// why a version was not considered as the best version
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Rejection {
+    VersionReq,
+    Prerelease,
+    UpdatePolicy,
+    Ignored,
+    TooNew,
+    NoDate
+}
+impl Rejection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::VersionReq => "version_req",
+            Rejection::Prerelease => "prerelease",
+            Rejection::UpdatePolicy => "update_policy",
+            Rejection::Ignored => "ignored",
+            Rejection::TooNew => "too_new",
+            Rejection::NoDate => "no_date"
+        }
+    }
+}
+/// everything a version has to satisfy to be considered as the best version
+#[derive(Default)]
+struct VersionConstraints<'a> {
+    version_req: Option<&'a VersionReq>,
+    update_policy: Option<&'a UpdatePolicy>,
+    current_version: Option<&'a Version>,
+    allow_prereleases: bool,
+    ignore_versions: &'a [IgnoredVersion],
+    published_before: Option<u64>,
+    dates: Option<&'a BTreeMap<Version, u64>>
+}
+impl VersionConstraints<'_> {
+    fn get_rejection(&self, v: &Version) -> Option<Rejection> {
+        // a prerelease meets the requirement if the release it leads up to
+        // does, but semver only takes it if a comparator names a prerelease
+        let release = Version::new(v.major, v.minor, v.patch);
+        let meets_req = self.version_req
+            .is_none_or(|vr| vr.matches(v) || (!v.pre.is_empty() && vr.matches(&release)));
+        if !meets_req {
+            return Some(Rejection::VersionReq);
+        }
+        let names_prerelease = self.version_req.is_some_and(|vr| vr.matches(v));
+        if !v.pre.is_empty() && !self.allow_prereleases && !names_prerelease {
+            return Some(Rejection::Prerelease);
+        }
+        if let (Some(policy), Some(current)) = (self.update_policy, self.current_version) {
+            if !policy.allows(current, v) {
+                return Some(Rejection::UpdatePolicy);
+            }
+        }
+        if self.ignore_versions.iter().any(|ignored| ignored.matches(v)) {
+            return Some(Rejection::Ignored);
+        }
+        if let Some(cutoff) = self.published_before {
+            match self.dates.and_then(|dates| dates.get(v)) {
+                None => return Some(Rejection::NoDate),
+                Some(&date) if date > cutoff => return Some(Rejection::TooNew),
+                _ => {}
+            }
+        }
+        None
+    }
+    fn matches(&self, v: &Version) -> bool {
+        self.get_rejection(v).is_none()
+    }
+}
+/// why no best version was found for a dep
+#[derive(Debug, PartialEq)]
+enum NoMatch {
+    NoTags,
+    NoTagMatchesPattern(usize, String),
+    NoValidVersion(usize),
+    NoneEligible(usize, String)
+}
+impl std::fmt::Display for NoMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoTags => write!(f, "no tags found"),
+            Self::NoTagMatchesPattern(tags, pattern) => write!(f, "none of the {} tags matches {}", tags, pattern),
+            Self::NoValidVersion(tags) => write!(f, "{} tags match, but none of them is a valid semver version", tags),
+            Self::NoneEligible(versions, rejections) => write!(f, "none of the {} versions is eligible: {}", versions, rejections)
+        }
     }
 }
 struct Dep {
     name: String,
     project: String,
+    api_url: Option<String>,
+    source: TagSource,
+    include_prereleases: bool,
+    max_pages: Option<u32>,
+    paging: Paging,
+    on_no_match: Option<NoMatchPolicy>,
+    allow_downgrade: bool,
     version_req: Option<VersionReq>,
+    update_policy: Option<UpdatePolicy>,
+    prerelease: Option<PrereleasePolicy>,
+    ignore_versions: Vec<IgnoredVersion>,
+    min_age_days: Option<u32>,
+    min_age_cutoff: Option<u64>,
     current_version: Option<Version>,
-    tag_prefix: String,
+    tag_pattern: TagPattern,
+    current_tag: Option<String>,
+    current_commit: Option<String>,
+    current_sha256: Option<String>,
+    sha256: Option<String>,
+    output_keys: Option<Vec<OutputKey>>,
     available_tags: Vec<String>,
+    tag_commits: BTreeMap<String, String>,
+    tag_tarball_urls: BTreeMap<String, String>,
+    tag_dates: BTreeMap<String, u64>,
     available_versions: Vec<Version>,
-    best_version: Option<Version>
+    available_dates: BTreeMap<Version, u64>,
+    best_version: Option<Version>,
+    refused_downgrade: Option<Version>,
+    pages_fetched: u32,
+    pages_total: Option<u32>,
+    stopped_early: bool,
+    error: Option<String>
 }
 impl Dep {
     fn from_table(table: &Table, dep: &str) -> Self {
-        let v = Version::from_str(table.get_ghdep_info(
+        let version_parse = VersionParse::from_config(table.get_ghdep_info(dep, DepKey::VersionParse));
+        let v = version_parse.parse(table.get_ghdep_info(
             dep,
             DepKey::Version)
             .unwrap_or_default()
-            .as_str()).ok();
+            .as_str());
         let vr = VersionReq::from_str(table.get_ghdep_info(
             dep,
             DepKey::VersionReq)
             .unwrap_or_default()
             .as_str()).ok();
-        return Self {
+        let tag_prefix = table.get_ghdep_info(dep, DepKey::TagPrefix).unwrap_or_default();
+        let separators = table.get_ghdep_info(dep, DepKey::VersionSeparators).unwrap_or_default();
+        // a broken pattern fails the dep instead of silently matching other tags
+        let (mut tag_pattern, error) = match table.get_ghdep_info(dep, DepKey::TagPattern) {
+            None => (TagPattern::from_prefix(tag_prefix.as_str(), separators.as_str()), None),
+            Some(pattern) => match TagPattern::from_regex(pattern.as_str(), separators.as_str()) {
+                Ok(tag_pattern) => (tag_pattern, None),
+                Err(e) => (TagPattern::from_prefix(tag_prefix.as_str(), separators.as_str()), Some(e))
+            }
+        };
+        tag_pattern.version_parse = version_parse;
+        Self {
             name: dep.to_string(),
             project: table.get_ghdep_info(dep, DepKey::Project).unwrap_or_default(),
+            api_url: table.get_ghdep_info(dep, DepKey::ApiUrl),
+            source: TagSource::from_config(table.get_ghdep_info(dep, DepKey::Source)),
+            include_prereleases: table.get_ghdep_flag(dep, DepKey::IncludePrereleases),
+            max_pages: table.get_ghdep_info(dep, DepKey::MaxPages).and_then(|p| p.parse::<u32>().ok()),
+            paging: Paging::from_config(table.get_ghdep_info(dep, DepKey::Paging)),
+            on_no_match: table.get_ghdep_info(dep, DepKey::OnNoMatch).and_then(|p| p.parse().ok()),
+            allow_downgrade: table.get_ghdep_flag(dep, DepKey::AllowDowngrade),
             version_req: vr,
+            update_policy: table.get_ghdep_info(dep, DepKey::UpdatePolicy).and_then(|p| p.parse().ok()),
+            prerelease: table.get_ghdep_info(dep, DepKey::Prerelease).map(|p| PrereleasePolicy::from_config(Some(p))),
+            ignore_versions: IgnoredVersion::parse_list(table.get_ghdep_info(dep, DepKey::IgnoreVersions).unwrap_or_default().as_str()),
+            min_age_days: table.get_ghdep_info(dep, DepKey::MinAgeDays).and_then(|d| d.parse::<u32>().ok()),
+            min_age_cutoff: None,
             current_version: v,
-            tag_prefix: table.get_ghdep_info(dep, DepKey::TagPrefix).unwrap_or_default(),
+            tag_pattern,
+            current_tag: table.get_ghdep_info(dep, DepKey::Tag),
+            current_commit: table.get_ghdep_info(dep, DepKey::Commit),
+            current_sha256: table.get_ghdep_info(dep, DepKey::Sha256)
+                .filter(|sha256| !sha256.is_empty()),
+            sha256: None,
+            output_keys: table.get_ghdep_info(dep, DepKey::OutputKeys).and_then(|k| OutputKey::parse_list(k.as_str()).ok()),
             available_tags: vec![],
+            tag_commits: BTreeMap::new(),
+            tag_tarball_urls: BTreeMap::new(),
+            tag_dates: BTreeMap::new(),
             available_versions: vec![],
-            best_version: None
+            available_dates: BTreeMap::new(),
+            best_version: None,
+            refused_downgrade: None,
+            pages_fetched: 0,
+            pages_total: None,
+            stopped_early: false,
+            error
         }
     }
-    fn get_versions_from_tags(tags: Vec<&str>, tag_prefix: &str) -> Vec<Version> {
+    fn get_versions_from_tags(tags: Vec<&str>, tag_pattern: &TagPattern) -> Vec<Version> {
         tags.iter().filter_map(|&tag| {
-            if !tag.starts_with(tag_prefix) {
-                return None;
-            }
-            Version::parse(&tag[tag_prefix.len()..tag.len()]).ok()
+            tag_pattern.get_version(tag)
         })
         .collect_vec()
     }
@@ -102,31 +649,352 @@ impl Dep {
             self.available_tags.iter()
                 .map(|t| t.as_str())
                 .collect_vec(),
-            self.tag_prefix.as_str());
+            &self.tag_pattern);
+        self.available_dates = self.tag_dates.iter()
+            .filter_map(|(tag, &date)| {
+                let version = Dep::get_versions_from_tags(vec![tag.as_str()], &self.tag_pattern).pop()?;
+                Some((version, date))
+            })
+            .collect();
+    }
+    /// the tag a version was taken from
+    fn get_tag_of(&self, version: &Version) -> Option<&String> {
+        self.available_tags.iter()
+            .find(|tag| Dep::get_versions_from_tags(vec![tag.as_str()], &self.tag_pattern).first() == Some(version))
+    }
+    fn add_commit(&mut self, tag: &str, commit: &str) {
+        self.tag_commits.insert(tag.to_string(), commit.to_string());
+    }
+    fn add_date(&mut self, tag: &str, date: u64) {
+        self.tag_dates.insert(tag.to_string(), date);
+        if let Some(version) = Dep::get_versions_from_tags(vec![tag], &self.tag_pattern).pop() {
+            self.available_dates.insert(version, date);
+        }
+    }
+    /// sets the time versions have to be published before to be considered,
+    /// from the minimum age of this dep or the default
+    fn apply_min_age(&mut self, default_days: u32, now: u64) {
+        let days = *self.min_age_days.get_or_insert(default_days);
+        self.min_age_cutoff = Some(days)
+            .filter(|&days| days > 0)
+            .map(|days| now.saturating_sub(u64::from(days) * date::SECS_PER_DAY));
+    }
+    /// the time a version is old enough to be considered
+    fn get_eligible_date(&self, version: &Version) -> Option<u64> {
+        let min_age_secs = u64::from(self.min_age_days.unwrap_or_default()) * date::SECS_PER_DAY;
+        self.available_dates.get(version).map(|date| date + min_age_secs)
     }
-    fn get_best_version(versions: Vec<&Version>, version_req: Option<&VersionReq>) -> Option<Version> {
+    /// the newest candidate that is not known to be old enough, as long as
+    /// no candidate is, since fetching dates one by one is expensive
+    fn get_undated_candidate(&self) -> Option<Version> {
+        let constraints = self.get_constraints();
+        self.available_versions.iter()
+            .filter(|v| matches!(constraints.get_rejection(v), None | Some(Rejection::NoDate)))
+            .max()
+            .filter(|v| constraints.get_rejection(v).is_some())
+            .cloned()
+    }
+    fn get_best_version(versions: Vec<&Version>, constraints: &VersionConstraints) -> Option<Version> {
         versions.iter()
-            .filter(|&&v| {
-                match &version_req {
-                    None => true,
-                    Some(vr) => vr.matches(&v)
-                }
-            })
+            .filter(|&&v| constraints.matches(v))
             .max()
-            .and_then(|&v| Some(v.clone()))
+            .map(|&v| v.clone())
+    }
+    fn get_constraints(&self) -> VersionConstraints<'_> {
+        VersionConstraints {
+            version_req: self.version_req.as_ref(),
+            update_policy: self.update_policy.as_ref(),
+            current_version: self.current_version.as_ref(),
+            allow_prereleases: match &self.prerelease {
+                Some(policy) => policy.allows_prereleases(self.current_version.as_ref()),
+                // without a requirement any version is taken, as it always was
+                None => self.version_req.is_none()
+            },
+            ignore_versions: &self.ignore_versions,
+            published_before: self.min_age_cutoff,
+            dates: Some(&self.available_dates)
+        }
+    }
+    /// the versions that meet all constraints checked before the given
+    /// rejection, but not the one it stands for
+    fn get_candidates_rejected_for(&self, rejection: Rejection) -> Vec<&Version> {
+        let constraints = self.get_constraints();
+        self.available_versions.iter()
+            .filter(|v| constraints.get_rejection(v).as_ref() == Some(&rejection))
+            .sorted()
+            .collect_vec()
+    }
+    /// the candidates newer than the best version that are too new, with
+    /// the date they become eligible
+    fn format_waiting_candidates(&self) -> String {
+        let is_newer = |v: &&&Version| Some(**v) > self.best_version.as_ref();
+        let undated = self.get_candidates_rejected_for(Rejection::NoDate).iter()
+            .filter(is_newer)
+            .count();
+        self.get_candidates_rejected_for(Rejection::TooNew).iter()
+            .filter(is_newer)
+            .filter_map(|v| Some(format!("{} (eligible on {})", v, date::format_date(self.get_eligible_date(v)?))))
+            .chain((undated > 0).then(|| format!("{} with unknown publishing date", undated)))
+            .join(", ")
     }
     fn update_best_version(&mut self) {
         self.best_version = Dep::get_best_version(
             self.available_versions.iter().collect_vec(),
-            self.version_req.as_ref());
+            &self.get_constraints());
+    }
+    /// logs for each tag why it was not taken as the best version
+    fn log_candidates(&self) {
+        if !log::log_enabled!(log::Level::Trace) {
+            return;
+        }
+        let constraints = self.get_constraints();
+        self.available_tags.iter().for_each(|tag| {
+            let version = match self.tag_pattern.get_version(tag) {
+                Some(version) => version,
+                None => {
+                    log::trace!("candidate dep={} tag={} rejected=no_version", self.name, tag);
+                    return;
+                }
+            };
+            match constraints.get_rejection(&version) {
+                Some(rejection) => log::trace!("candidate dep={} tag={} version={} rejected={}", self.name, tag, version, rejection.as_str()),
+                None if Some(&version) == self.best_version.as_ref() => log::trace!("candidate dep={} tag={} version={} best", self.name, tag, version),
+                None => log::trace!("candidate dep={} tag={} version={} rejected=older", self.name, tag, version)
+            }
+        });
+    }
+    /// how many versions were rejected for each reason, in one line
+    fn format_rejections(&self) -> String {
+        let constraints = self.get_constraints();
+        self.available_versions.iter()
+            .filter_map(|v| constraints.get_rejection(v))
+            .sorted()
+            .dedup_with_count()
+            .map(|(count, rejection)| match rejection {
+                Rejection::VersionReq => format!("{} not meeting the requirement \"{}\"",
+                    count,
+                    self.version_req.as_ref().map(|vr| vr.to_string()).unwrap_or_default()),
+                Rejection::UpdatePolicy => format!("{} not allowed by {} updates of {}",
+                    count,
+                    self.update_policy.as_ref().map(|policy| policy.as_str()).unwrap_or_default(),
+                    self.current_version.as_ref().map(|v| v.to_string()).unwrap_or_default()),
+                Rejection::Prerelease => format!("{} prereleases", count),
+                Rejection::Ignored => format!("{} ignored", count),
+                Rejection::TooNew => format!("{} too new", count),
+                Rejection::NoDate => format!("{} with unknown publishing date", count)
+            })
+            .join(", ")
+    }
+    fn get_no_match(&self) -> Option<NoMatch> {
+        if self.best_version.is_some() {
+            return None;
+        }
+        let matching_tags = self.available_tags.iter()
+            .filter(|t| self.tag_pattern.get_version_str(t).is_some())
+            .count();
+        Some(match (self.available_tags.len(), matching_tags, self.available_versions.len()) {
+            (0, _, _) => NoMatch::NoTags,
+            (tags, 0, _) => NoMatch::NoTagMatchesPattern(tags, self.tag_pattern.description.clone()),
+            (_, tags, 0) => NoMatch::NoValidVersion(tags),
+            (_, _, versions) => NoMatch::NoneEligible(
+                versions,
+                self.format_rejections())
+        })
+    }
+    /// keeps the current version or marks the dep as failed if no version
+    /// was found, depending on the policy
+    fn apply_no_match_policy(&mut self, default_policy: &NoMatchPolicy) {
+        let no_match = match self.get_no_match() {
+            None => return,
+            Some(no_match) => no_match
+        };
+        match self.on_no_match.as_ref().unwrap_or(default_policy) {
+            NoMatchPolicy::Keep => log::warn!("no version found for {}: {}, keeping {}",
+                self.name,
+                no_match,
+                self.current_version.as_ref().map(|v| v.to_string()).unwrap_or("no version".to_string())),
+            NoMatchPolicy::Fail => self.error = Some(format!("no version found: {}", no_match))
+        }
+    }
+    /// stays on the current version instead of going back to an older one,
+    /// unless downgrades are allowed
+    fn apply_downgrade_protection(&mut self, allow_downgrade: bool) {
+        if allow_downgrade || self.allow_downgrade {
+            return;
+        }
+        if let (Some(best), Some(current)) = (&self.best_version, &self.current_version) {
+            if best < current {
+                self.refused_downgrade = self.best_version.replace(current.clone());
+            }
+        }
+    }
+    fn start_paging(&mut self) {
+        self.available_tags = vec![];
+        self.tag_commits = BTreeMap::new();
+        self.tag_tarball_urls = BTreeMap::new();
+        self.tag_dates = BTreeMap::new();
+        self.pages_fetched = 0;
+        self.stopped_early = false;
+    }
+    /// adds the tags of a fetched page, returns if fetching the next page
+    /// could still improve on the result
+    fn add_page_of_tags(&mut self, entries: Vec<TagEntry>, has_next_page: bool) -> bool {
+        self.pages_fetched += 1;
+        entries.iter().for_each(|entry| {
+            if let Some(commit) = &entry.commit {
+                self.tag_commits.insert(entry.name.clone(), commit.clone());
+            }
+            if let Some(date) = entry.date {
+                self.tag_dates.insert(entry.name.clone(), date);
+            }
+            if let Some(tarball_url) = &entry.tarball_url {
+                self.tag_tarball_urls.insert(entry.name.clone(), tarball_url.clone());
+            }
+        });
+        let mut tags = entries.into_iter()
+            .map(|entry| entry.name)
+            .collect_vec();
+        let mut wants_next_page = has_next_page;
+        if self.paging == Paging::NewestFirst {
+            // with tags roughly sorted newest first, a page without anything
+            // better than what was found already means there is nothing to gain
+            let best_so_far = self.get_best_version_of_tags(&self.available_tags);
+            let page_best = self.get_best_version_of_tags(&tags);
+            if best_so_far.is_some() && page_best <= best_so_far {
+                self.stopped_early = has_next_page;
+                wants_next_page = false;
+            }
+        }
+        if self.max_pages.is_some_and(|max_pages| self.pages_fetched >= max_pages) {
+            wants_next_page = false;
+        }
+        self.available_tags.append(&mut tags);
+        wants_next_page
+    }
+    /// the best version among just the given tags, as used to decide on
+    /// stopping early while paging, dates are not known yet at that point
+    fn get_best_version_of_tags(&self, tags: &[String]) -> Option<Version> {
+        let versions = Dep::get_versions_from_tags(
+            tags.iter()
+                .map(|t| t.as_str())
+                .collect_vec(),
+            &self.tag_pattern);
+        Dep::get_best_version(versions.iter().collect_vec(), &VersionConstraints {
+            published_before: None,
+            ..self.get_constraints()
+        })
+    }
+    /// the version to write out, a dep that failed to update or has no
+    /// matching version keeps its current version
+    fn resolved_version(&self) -> Option<&Version> {
+        match self.error {
+            Some(_) => self.current_version.as_ref(),
+            None => self.best_version.as_ref().or(self.current_version.as_ref())
+        }
+    }
+    /// the tag of the version to write out, which only differs from the
+    /// version if the tag is not parsed strictly
+    fn resolved_tag(&self) -> Option<String> {
+        let version = self.resolved_version()?;
+        let known = match (&self.error, &self.best_version) {
+            (None, Some(best)) if best == version => self.get_tag_of(version).cloned(),
+            _ => None
+        };
+        known
+            .or_else(|| self.current_tag.clone())
+            .or_else(|| self.tag_pattern.format_tag(version))
+    }
+    /// the commit of the version to write out, for a kept version it is
+    /// only known from the previous run if it was not fetched again
+    fn resolved_commit(&self) -> Option<&String> {
+        let known = self.resolved_tag()
+            .and_then(|tag| self.tag_commits.get(&tag));
+        match self.resolved_version() == self.current_version.as_ref() {
+            true => known.or(self.current_commit.as_ref()),
+            false => known
+        }
+    }
+    /// the checksum of the tarball of the version to write out, a kept
+    /// version keeps the checksum of the previous run
+    fn resolved_sha256(&self) -> Option<&String> {
+        match self.resolved_version() == self.current_version.as_ref() {
+            true => self.sha256.as_ref().or(self.current_sha256.as_ref()),
+            false => self.sha256.as_ref()
+        }
+    }
+    /// the tag whose tarball has to be downloaded for the checksum, if it is
+    /// to be written and the version changed or has no checksum yet
+    fn get_tag_to_checksum(&self) -> Option<String> {
+        if self.error.is_some() || !self.get_output_keys().contains(&OutputKey::Sha256) {
+            return None;
+        }
+        if self.resolved_version() == self.current_version.as_ref() && self.current_sha256.is_some() {
+            return None;
+        }
+        self.resolved_tag()
+    }
+    /// the tarball url as listed with the tag, or built from the tag name
+    fn get_tarball_url(&self, api_url: &str, tag: &str) -> String {
+        self.tag_tarball_urls.get(tag)
+            .cloned()
+            .unwrap_or_else(|| tarball_url(api_url, self.project.as_str(), tag))
+    }
+    /// the keys to write, falling back to the default of the run
+    fn apply_output_keys(&mut self, default_keys: &[OutputKey]) {
+        if self.output_keys.is_none() && !default_keys.is_empty() {
+            self.output_keys = Some(default_keys.to_vec());
+        }
+    }
+    /// the keys written besides the version, by default the tag is only
+    /// written if it is not parsed strictly
+    fn get_output_keys(&self) -> Vec<OutputKey> {
+        match (&self.output_keys, &self.tag_pattern.version_parse) {
+            (Some(keys), _) => keys.clone(),
+            (None, VersionParse::Lenient) => vec![OutputKey::Tag],
+            (None, VersionParse::Strict) => vec![]
+        }
+    }
+    /// the kind of update to the best version, if it differs from the
+    /// current one
+    fn get_update_kind(&self) -> Option<UpdateKind> {
+        if self.error.is_some() {
+            return None;
+        }
+        self.best_version.as_ref()
+            .filter(|&best| Some(best) != self.current_version.as_ref())
+            .map(|best| UpdateKind::between(self.current_version.as_ref(), best))
+    }
+    fn format_paging(&self) -> String {
+        match (self.stopped_early, self.pages_total) {
+            (false, _) => format!("{}", self.pages_fetched),
+            (true, Some(total)) => format!("{} ({} requests saved by stopping early)", self.pages_fetched, total.saturating_sub(self.pages_fetched)),
+            (true, None) => format!("{} (stopped early)", self.pages_fetched)
+        }
     }
 }
 impl std::fmt::Display for Dep {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}_GH_VERSION=\"{}\"",
             self.name.to_ascii_uppercase(),
-            self.best_version.as_ref().map(|v| v.to_string()).unwrap_or_default()
-        )
+            self.resolved_version().map(|v| v.to_string()).unwrap_or_default()
+        )?;
+        let output_keys = self.get_output_keys();
+        if output_keys.contains(&OutputKey::Tag) {
+            write!(f, "\n{}_GH_TAG=\"{}\"",
+                self.name.to_ascii_uppercase(),
+                self.resolved_tag().unwrap_or_default())?;
+        }
+        if output_keys.contains(&OutputKey::Commit) {
+            write!(f, "\n{}_GH_COMMIT=\"{}\"",
+                self.name.to_ascii_uppercase(),
+                self.resolved_commit().map(String::as_str).unwrap_or_default())?;
+        }
+        if output_keys.contains(&OutputKey::Sha256) {
+            write!(f, "\n{}_GH_SHA256=\"{}\"",
+                self.name.to_ascii_uppercase(),
+                self.resolved_sha256().map(String::as_str).unwrap_or_default())?;
+        }
+        Ok(())
     }
 }
 impl std::fmt::Debug for Dep {
@@ -139,35 +1007,69 @@ impl std::fmt::Debug for Dep {
 # previous version: {}
 # with tags: {}
 # with versions: {}
-{}_GH_VERSION=\"{}\"
+# pages fetched: {}{}{}{}{}{}
+{}
 ",
             self.name,
             self.project,
             self.current_version.as_ref().map(|v| v.to_string()).unwrap_or_default(),
             self.available_tags.join(", "),
             available_versions.join(", "),
-            self.name.to_ascii_uppercase(),
-            &self.best_version.as_ref().map(|v| v.to_string()).unwrap_or_default()
+            self.format_paging(),
+            self.get_no_match().map(|m| format!("\n# no match: {}", m)).unwrap_or_default(),
+            self.refused_downgrade.as_ref().map(|v| format!("\n# refused downgrade to: {}", v)).unwrap_or_default(),
+            Some(self.get_candidates_rejected_for(Rejection::Ignored))
+                .filter(|ignored| !ignored.is_empty())
+                .map(|ignored| format!("\n# skipped as ignored: {}", ignored.iter().join(", ")))
+                .unwrap_or_default(),
+            Some(self.format_waiting_candidates())
+                .filter(|waiting| !waiting.is_empty())
+                .map(|waiting| format!("\n# skipped as too new: {}", waiting))
+                .unwrap_or_default(),
+            self.error.as_ref().map(|e| format!("\n# failed to update: {}", e)).unwrap_or_default(),
+            self
         )
     }
 }
 enum ConfigError {
     TooFewArgs(usize),
+    NoConfigFile(),
     NoOutputFile(),
+    MissingOptionValue(String),
+    InvalidOptionValue(String, String),
+    UnknownOption(String),
+    NoCacheDir(),
+    SnapshotReadError(String),
+    SnapshotWriteError(String),
+    ReportWriteError(String),
+    OutputWriteError(String),
     ConfigReadError(String),
     FromUtf8Error(),
     TomlParseError(toml::de::Error),
-    GithubTokenMissing()
+    GithubTokenMissing(),
+    VerifyOffline(),
+    LintProblems(Vec<String>)
 }
 impl std::fmt::Debug for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let formatted = match self {
-            Self::TooFewArgs(args) => format!("at least two config files needed, but only {} found", args),
-            Self::NoOutputFile() => format!("no output file"),
+            Self::TooFewArgs(args) => format!("no --output given and at least two config files needed to write to the last one, but only {} found", args),
+            Self::NoConfigFile() => "no config file given, see --help".to_string(),
+            Self::NoOutputFile() => "no output file".to_string(),
+            Self::MissingOptionValue(option) => format!("option {} needs a value", option),
+            Self::InvalidOptionValue(option, value) => format!("invalid value for option {}: {}", option, value),
+            Self::UnknownOption(option) => format!("unknown option: {}", option),
+            Self::SnapshotReadError(filename) => format!("error reading tag snapshot: {}", filename),
+            Self::SnapshotWriteError(filename) => format!("error writing tag snapshot: {}", filename),
+            Self::ReportWriteError(filename) => format!("error writing report: {}", filename),
+            Self::OutputWriteError(filename) => format!("error writing output file: {}", filename),
+            Self::NoCacheDir() => "no default cache directory, neither XDG_CACHE_HOME nor HOME is set".to_string(),
             Self::ConfigReadError(filename) => format!("error reading config file: {}", filename),
-            Self::FromUtf8Error() => format!("config is not valid utf8"),
+            Self::FromUtf8Error() => "config is not valid utf8".to_string(),
             Self::TomlParseError(e) => format!("config cant be parsed as toml: {}", e),
-            Self::GithubTokenMissing() => format!("GITHUB_TOKEN environment variable is missing or unset")
+            Self::GithubTokenMissing() => "GITHUB_TOKEN environment variable is missing or unset".to_string(),
+            Self::VerifyOffline() => "checksums can't be verified with --offline".to_string(),
+            Self::LintProblems(problems) => problems.join("\n")
         };
         write!(f, "{}", formatted)
     }
@@ -179,17 +1081,274 @@ impl std::fmt::Display for ConfigError {
 }
 impl std::error::Error for ConfigError {}
 
-async fn setup_config(args: Vec<&str>) -> Result<(toml::Table, String), ConfigError> {
-    let input_file_names = args.iter()
-        .skip(1)
-        .collect_vec();
-    if input_file_names.len() < 2 {
-        return Err(ConfigError::TooFewArgs(input_file_names.len()));
+const DEFAULT_API_URL : &str = "https://api.github.com";
+#[derive(Debug, PartialEq)]
+enum Backend {
+    Rest,
+    Graphql
+}
+impl FromStr for Backend {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rest" => Ok(Backend::Rest),
+            "graphql" => Ok(Backend::Graphql),
+            _ => Err(())
+        }
+    }
+}
+/// what a run does, given as the first file name
+#[derive(Debug, PartialEq)]
+enum Command {
+    Update,
+    Check,
+    Show,
+    Lint,
+    Verify,
+    Help,
+    Version
+}
+impl FromStr for Command {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "update" => Ok(Command::Update),
+            "check" => Ok(Command::Check),
+            "show" => Ok(Command::Show),
+            "lint" => Ok(Command::Lint),
+            "verify" => Ok(Command::Verify),
+            "help" => Ok(Command::Help),
+            _ => Err(())
+        }
+    }
+}
+const USAGE : &str = "usage: ghdepup [command] [options] ghdeps.toml [...] --output ghversions.toml
+
+commands:
+  update    update the versions and write them to the output file (default)
+  check     list the available updates without writing anything, exits with 1
+            if there are any and 2 on errors
+  show      print what update would write instead of writing it
+  lint      check the config for mistakes without asking GitHub
+  verify    download the tarballs of the current versions and check their checksums
+
+The output file is also read for the current versions if it exists.
+
+options:
+  -o, --output <file>          file to write the versions to
+  -h, --help                   print this help
+  -V, --version                print the version of ghdepup
+  -v, --verbose                log requests and the state of each dependency,
+                               twice also why each tag was not taken
+  -q, --quiet                  only log errors
+  --api-url <url>              base URL of the GitHub REST API
+  --backend <rest|graphql>     how tags are fetched
+  --jobs <count>               dependencies fetched at the same time
+  --max-retries <count>        retries of a rate limited request
+  --max-retry-wait <seconds>   time spent waiting for a rate limit per request
+  --cache                      cache pages in the default location
+  --cache-dir <dir>            cache pages in the given directory
+  --keep-going                 keep the current version of failed dependencies
+  --on-no-match <keep|fail>    default for _GH_ON_NO_MATCH
+  --allow-downgrade            allow downgrades of all dependencies
+  --output-keys <keys>         default for _GH_OUTPUT_KEYS
+  --min-age-days <days>        default for _GH_MIN_AGE_DAYS
+  --fail-on <major|minor|patch>  smallest update that fails check
+  --dry-run                    print a diff of the output file instead of writing it
+  --record-tags <file>         write the tags found to a snapshot file
+  --report-json <file>         write a JSON report of each dependency
+  --offline <file>             read the tags from a snapshot file";
+const DEFAULT_JOBS : usize = 4;
+const DEFAULT_MAX_RETRIES : u32 = 5;
+const DEFAULT_MAX_RETRY_WAIT_SECS : u64 = 300;
+struct Settings {
+    api_url: String,
+    max_retries: u32,
+    max_retry_wait_secs: u64,
+    cache_dir: Option<PathBuf>,
+    offline_snapshot: Option<String>,
+    record_tags: Option<String>,
+    report_json: Option<String>,
+    keep_going: bool,
+    on_no_match: NoMatchPolicy,
+    allow_downgrade: bool,
+    backend: Backend,
+    jobs: usize,
+    min_age_days: u32,
+    output_keys: Vec<OutputKey>,
+    output: Option<String>,
+    fail_on: UpdateKind,
+    dry_run: bool,
+    log_level: LevelFilter
+}
+impl Settings {
+    fn from_env() -> Self {
+        Self {
+            api_url: env::var("GHDEPUP_API_URL").unwrap_or(DEFAULT_API_URL.to_string()),
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_retry_wait_secs: DEFAULT_MAX_RETRY_WAIT_SECS,
+            cache_dir: env::var("GHDEPUP_CACHE_DIR").ok().map(PathBuf::from),
+            offline_snapshot: None,
+            record_tags: None,
+            report_json: None,
+            keep_going: false,
+            on_no_match: NoMatchPolicy::Keep,
+            allow_downgrade: false,
+            backend: Backend::Rest,
+            jobs: DEFAULT_JOBS,
+            min_age_days: 0,
+            output_keys: vec![],
+            output: None,
+            fail_on: UpdateKind::Patch,
+            dry_run: false,
+            log_level: LevelFilter::Info
+        }
+    }
+}
+/// the value given as `--option=value` or else the next argument
+fn get_option_value<'a>(option: &str, inline_value: &mut Option<&'a str>, args: &mut impl Iterator<Item = &'a str>) -> Result<&'a str, ConfigError> {
+    inline_value.take()
+        .or_else(|| args.next())
+        .ok_or(ConfigError::MissingOptionValue(option.to_string()))
+}
+fn parse_option_value<T: FromStr>(option: &str, value: &str) -> Result<T, ConfigError> {
+    value.parse::<T>()
+        .map_err(|_| ConfigError::InvalidOptionValue(option.to_string(), value.to_string()))
+}
+/// parses the command line without the program name, returning the command
+/// and the config files
+fn parse_options(args: Vec<&str>, mut settings: Settings) -> Result<(Settings, Command, Vec<&str>), ConfigError> {
+    let mut positional = vec![];
+    let mut help = false;
+    let mut version = false;
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        let (arg, mut inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+            _ => (arg, None)
+        };
+        match arg {
+            "--output" | "-o" => {
+                settings.output = Some(get_option_value(arg, &mut inline_value, &mut args)?.to_string());
+            },
+            "--help" | "-h" => {
+                help = true;
+            },
+            "--version" | "-V" => {
+                version = true;
+            },
+            "--verbose" | "-v" => {
+                settings.log_level = logger::more_verbose(settings.log_level);
+            },
+            "-vv" => {
+                settings.log_level = logger::more_verbose(logger::more_verbose(settings.log_level));
+            },
+            "--quiet" | "-q" => {
+                settings.log_level = LevelFilter::Error;
+            },
+            "--api-url" => {
+                settings.api_url = get_option_value(arg, &mut inline_value, &mut args)?.to_string();
+            },
+            "--max-retries" => {
+                settings.max_retries = parse_option_value(arg, get_option_value(arg, &mut inline_value, &mut args)?)?;
+            },
+            "--max-retry-wait" => {
+                settings.max_retry_wait_secs = parse_option_value(arg, get_option_value(arg, &mut inline_value, &mut args)?)?;
+            },
+            "--cache" => {
+                settings.cache_dir = Some(Cache::default_dir().ok_or(ConfigError::NoCacheDir())?);
+            },
+            "--cache-dir" => {
+                settings.cache_dir = Some(PathBuf::from(get_option_value(arg, &mut inline_value, &mut args)?));
+            },
+            "--offline" => {
+                settings.offline_snapshot = Some(get_option_value(arg, &mut inline_value, &mut args)?.to_string());
+            },
+            "--jobs" => {
+                let value = get_option_value(arg, &mut inline_value, &mut args)?;
+                settings.jobs = parse_option_value(arg, value)?;
+                if settings.jobs == 0 {
+                    return Err(ConfigError::InvalidOptionValue(arg.to_string(), value.to_string()));
+                }
+            },
+            "--keep-going" => {
+                settings.keep_going = true;
+            },
+            "--on-no-match" => {
+                settings.on_no_match = parse_option_value(arg, get_option_value(arg, &mut inline_value, &mut args)?)?;
+            },
+            "--allow-downgrade" => {
+                settings.allow_downgrade = true;
+            },
+            "--dry-run" => {
+                settings.dry_run = true;
+            },
+            "--output-keys" => {
+                let value = get_option_value(arg, &mut inline_value, &mut args)?;
+                settings.output_keys = OutputKey::parse_list(value)
+                    .map_err(|_| ConfigError::InvalidOptionValue(arg.to_string(), value.to_string()))?;
+            },
+            "--fail-on" => {
+                settings.fail_on = parse_option_value(arg, get_option_value(arg, &mut inline_value, &mut args)?)?;
+            },
+            "--min-age-days" => {
+                settings.min_age_days = parse_option_value(arg, get_option_value(arg, &mut inline_value, &mut args)?)?;
+            },
+            "--backend" => {
+                settings.backend = parse_option_value(arg, get_option_value(arg, &mut inline_value, &mut args)?)?;
+            },
+            "--record-tags" => {
+                settings.record_tags = Some(get_option_value(arg, &mut inline_value, &mut args)?.to_string());
+            },
+            "--report-json" => {
+                settings.report_json = Some(get_option_value(arg, &mut inline_value, &mut args)?.to_string());
+            },
+            _ if arg.starts_with('-') && arg != "-" => return Err(ConfigError::UnknownOption(arg.to_string())),
+            _ => positional.push(arg)
+        }
+        // left over if given to an option without a value
+        if let Some(value) = inline_value {
+            return Err(ConfigError::InvalidOptionValue(arg.to_string(), value.to_string()));
+        }
+    }
+    let command = match positional.first().map(|first| Command::from_str(first)) {
+        Some(Ok(command)) => {
+            positional.remove(0);
+            command
+        },
+        _ => Command::Update
     };
-    let output_file_name = args
-        .last()
-        .ok_or(ConfigError::NoOutputFile())?
-        .to_string();
+    let command = match (help, version) {
+        (true, _) => Command::Help,
+        (false, true) => Command::Version,
+        (false, false) => command
+    };
+    Ok((settings, command, positional))
+}
+/// the config files to read and the file to write. Without --output an
+/// update writes to the last config file, which is deprecated as a typo
+/// can overwrite a config file that way.
+fn get_file_names<'a>(command: &Command, files: Vec<&'a str>, output: Option<&'a str>) -> Result<(Vec<&'a str>, Option<&'a str>), ConfigError> {
+    if files.is_empty() {
+        return Err(ConfigError::NoConfigFile());
+    }
+    match (command, output) {
+        (Command::Update, None) => {
+            if files.len() < 2 {
+                return Err(ConfigError::TooFewArgs(files.len()));
+            }
+            let output = files.last().copied()
+                .ok_or(ConfigError::NoOutputFile())?;
+            log::warn!("writing to the last config file is deprecated, use --output");
+            Ok((files, Some(output)))
+        },
+        (Command::Update, Some(output)) => Ok((files, Some(output))),
+        _ => Ok((files, None))
+    }
+}
+/// reads and merges the config files, the output file holds the current
+/// versions but does not have to exist yet
+async fn setup_config(input_file_names: &[&str], output_file_name: Option<&str>) -> Result<toml::Table, ConfigError> {
     let mut buf = vec![];
     for &f in input_file_names.iter() {
         let mut c = tokio::fs::read(f).await
@@ -198,42 +1357,65 @@ async fn setup_config(args: Vec<&str>) -> Result<(toml::Table, String), ConfigEr
         buf.append(c.as_mut());
         buf.push(b'\n');
     }
+    if let Some(f) = output_file_name.filter(|f| !input_file_names.contains(f)) {
+        match tokio::fs::read(f).await {
+            Ok(mut c) => {
+                buf.append(c.as_mut());
+                buf.push(b'\n');
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(_) => return Err(ConfigError::ConfigReadError(f.to_string()))
+        }
+    }
     let config_str = String::from_utf8(buf)
         .or(Err(ConfigError::FromUtf8Error()))?;
-    let config = toml::from_str::<Table>(config_str.as_str())
-        .or_else(|e| Err(ConfigError::TomlParseError(e)))?;
-    Ok((config, output_file_name))
+    toml::from_str::<Table>(config_str.as_str())
+        .map_err(ConfigError::TomlParseError)
 }
 
 enum GetTagsError {
     ExpectedJsonArrayError(),
+    ExpectedJsonDate(),
     ExpectedJsonName(),
     ExpectedJsonObjectError(),
+    ExpectedJsonSha(),
     FromUtf8Error(FromUtf8Error),
     HyperError(hyper::Error),
     HyperHttpError(hyper::http::Error),
     HyperHttpStatusError(hyper::http::StatusCode),
     HyperHttpUnauthorizedError(hyper::http::StatusCode),
     JsonParseError(),
-    MultipleGithubErrors(Vec<String>)
+    MultipleGithubErrors(Vec<String>),
+    GraphqlError(String),
+    NotInSnapshot(String),
+    NoTagToVerify(String),
+    RateLimitExceeded(hyper::http::StatusCode, u32),
+    TooManyRedirects(String)
 }
 impl std::fmt::Debug for GetTagsError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let formatted = match self {
-            Self::ExpectedJsonArrayError() => format!("json array not found where expected in response"),
-            Self::ExpectedJsonName() => format!("name not found where expected in json response"),
-            Self::ExpectedJsonObjectError() => format!("object not found where expected in json response"),
+            Self::ExpectedJsonArrayError() => "json array not found where expected in response".to_string(),
+            Self::ExpectedJsonDate() => "date not found where expected in json response".to_string(),
+            Self::ExpectedJsonName() => "name not found where expected in json response".to_string(),
+            Self::ExpectedJsonObjectError() => "object not found where expected in json response".to_string(),
+            Self::ExpectedJsonSha() => "sha not found where expected in json response".to_string(),
             Self::FromUtf8Error(e) => format!("error parsing response as UTF8: {}.", e),
             Self::HyperError(e) => format!("hyper error: {}", e),
             Self::HyperHttpError(e) => format!("hyper http error: {}", e),
             Self::HyperHttpStatusError(e) => format!("unexpected http status: {}", e),
             Self::HyperHttpUnauthorizedError(e) => format!("http status unauthorized (maybe your github token expired?): {}", e),
-            Self::JsonParseError() => format!("error parsing json response"),
+            Self::JsonParseError() => "error parsing json response".to_string(),
             Self::MultipleGithubErrors(errs) => {
                 errs.iter()
-                    .map(|e| format!("{}", e))
+                    .map(|e| e.to_string())
                     .join("\n")
-            }
+            },
+            Self::GraphqlError(message) => format!("graphql error: {}", message),
+            Self::NotInSnapshot(project) => format!("no tags for {} in the offline snapshot", project),
+            Self::NoTagToVerify(dep) => format!("no tag known for {}, it is needed to verify the checksum", dep),
+            Self::RateLimitExceeded(e, retries) => format!("github rate limit exceeded, giving up after {} retries: {}", retries, e),
+            Self::TooManyRedirects(url) => format!("too many redirects downloading {}", url)
         };
         write!(f, "{}", formatted)
     }
@@ -245,117 +1427,751 @@ impl std::fmt::Display for GetTagsError {
 }
 impl std::error::Error for GetTagsError {}
 
-fn get_tag_name(entry: &Value) -> Result<&str, GetTagsError> {
+fn get_tag_name<'a>(entry: &'a Value, key: &str) -> Result<&'a str, GetTagsError> {
     entry
         .as_object()
         .ok_or(GetTagsError::ExpectedJsonObjectError())?
-        .get("name")
+        .get(key)
         .ok_or(GetTagsError::ExpectedJsonName())?
         .as_str()
         .ok_or(GetTagsError::ExpectedJsonName())
 }
-async fn parse_tags_json(json_to_parse: &str) -> Result<Vec<String>, GetTagsError> {
+async fn parse_tags_json(json_to_parse: &str) -> Result<Vec<TagEntry>, GetTagsError> {
+    let v : Value = serde_json::from_str(json_to_parse)
+        .map_err(|_| GetTagsError::JsonParseError())?;
+    let entries = v.as_array()
+        .ok_or(GetTagsError::ExpectedJsonArrayError())?;
+    // the list of tags has no dates, those need a request per commit
+    let str_res = entries
+        .iter()
+        .filter_map(|e| {
+            let commit = e.get("commit")
+                .and_then(|c| c.get("sha"))
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            let tarball_url = e.get("tarball_url")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            get_tag_name(e, "name").ok().map(|name| TagEntry { name: name.to_owned(), commit, date: None, tarball_url })
+        });
+    Ok(str_res
+        .into_iter()
+        .collect_vec())
+}
+fn is_release_flag_set(entry: &Value, flag: &str) -> bool {
+    entry
+        .get(flag)
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+async fn parse_releases_json(json_to_parse: &str, include_prereleases: bool) -> Result<Vec<TagEntry>, GetTagsError> {
     let v : Value = serde_json::from_str(json_to_parse)
         .map_err(|_| GetTagsError::JsonParseError())?;
     let entries = v.as_array()
         .ok_or(GetTagsError::ExpectedJsonArrayError())?;
     let str_res = entries
         .iter()
-        .filter_map(|e| {get_tag_name(e).ok()});
+        .filter(|&e| !is_release_flag_set(e, "draft"))
+        .filter(|&e| include_prereleases || !is_release_flag_set(e, "prerelease"))
+        .filter_map(|e| {
+            let date = e.get("published_at")
+                .and_then(Value::as_str)
+                .and_then(date::parse_timestamp);
+            // a release only names the branch it was made from, the commit
+            // needs a request of its own
+            get_tag_name(e, "tag_name").ok().map(|name| TagEntry { name: name.to_owned(), commit: None, date, tarball_url: None })
+        });
     Ok(str_res
         .into_iter()
-        .map(str::to_owned)
         .collect_vec())
 }
-async fn get_repo_tags_json(project: &str, token: &str, page: i32) -> Result<String, GetTagsError> {
+/// the sha and date of the commit a ref points to, from a response of the
+/// commits endpoint
+fn parse_commit_json(json_to_parse: &str) -> Result<(String, u64), GetTagsError> {
+    let v : Value = serde_json::from_str(json_to_parse)
+        .map_err(|_| GetTagsError::JsonParseError())?;
+    let sha = v.get("sha")
+        .and_then(Value::as_str)
+        .ok_or(GetTagsError::ExpectedJsonSha())?;
+    let date = v.get("commit")
+        .and_then(|c| c.get("committer"))
+        .and_then(|c| c.get("date"))
+        .and_then(Value::as_str)
+        .and_then(date::parse_timestamp)
+        .ok_or(GetTagsError::ExpectedJsonDate())?;
+    Ok((sha.to_string(), date))
+}
+/// one rate limit budget, responses can come back out of order with
+/// several jobs, so the highest and lowest remaining count are kept
+struct RateLimitBudget {
+    limit: Option<u64>,
+    max_remaining: u64,
+    min_remaining: u64
+}
+impl std::fmt::Display for RateLimitBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the first response already had its request deducted
+        write!(f, "{} ({}/{} remaining)",
+            (self.max_remaining + 1).saturating_sub(self.min_remaining),
+            self.min_remaining,
+            self.limit.map(|l| l.to_string()).unwrap_or("unknown".to_string()))
+    }
+}
+#[derive(Default)]
+struct RateLimitStats {
+    requests: u32,
+    not_modified: u32,
+    /// GitHub has separate budgets for e.g. REST and GraphQL, and each host
+    /// has its own, so they are kept by host and resource
+    budgets: BTreeMap<(String, String), RateLimitBudget>
+}
+impl RateLimitStats {
+    fn record(&mut self, url: &str, headers: &hyper::HeaderMap) {
+        self.requests += 1;
+        let remaining = match get_header_number(headers, "x-ratelimit-remaining") {
+            Some(remaining) => remaining,
+            None => return
+        };
+        let host = url.parse::<hyper::Uri>().ok()
+            .and_then(|uri| uri.host().map(str::to_owned))
+            .unwrap_or_default();
+        let resource = headers.get("x-ratelimit-resource")
+            .and_then(|r| r.to_str().ok())
+            .unwrap_or("core")
+            .to_string();
+        let budget = self.budgets.entry((host, resource)).or_insert(RateLimitBudget {
+            limit: None,
+            max_remaining: remaining,
+            min_remaining: remaining
+        });
+        budget.limit = get_header_number(headers, "x-ratelimit-limit").or(budget.limit);
+        budget.max_remaining = budget.max_remaining.max(remaining);
+        budget.min_remaining = budget.min_remaining.min(remaining);
+    }
+}
+impl std::fmt::Display for RateLimitStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let used = match self.budgets.is_empty() {
+            true => "unknown".to_string(),
+            false => self.budgets.iter()
+                .map(|((host, resource), budget)| format!("{} {} {}", host, resource, budget))
+                .join(", ")
+        };
+        write!(f, "{} requests ({} not modified), rate limit budget used: {}",
+            self.requests,
+            self.not_modified,
+            used)
+    }
+}
+struct GhClient {
+    client: Client<HttpsConnector<HttpConnector>>,
+    api_url: String,
+    token: String,
+    max_retries: u32,
+    max_retry_wait_secs: u64,
+    cache: Option<Cache>,
+    rate_limit: Mutex<RateLimitStats>
+}
+impl GhClient {
+    fn new(settings: &Settings, token: String) -> Self {
+        // the https connector also speaks plain http, e.g. for a local stand-in of the API
+        let https = HttpsConnector::new();
+        Self {
+            client: Client::builder().build::<_, hyper::Body>(https),
+            api_url: settings.api_url.clone(),
+            token,
+            max_retries: settings.max_retries,
+            max_retry_wait_secs: settings.max_retry_wait_secs,
+            cache: settings.cache_dir.clone().map(Cache::new),
+            rate_limit: Mutex::new(RateLimitStats::default())
+        }
+    }
+}
+fn get_header_number(headers: &hyper::HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+}
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+/// seconds to wait before retrying a rate limited request, None if the
+/// response is not about rate limits at all
+fn get_rate_limit_wait(status: hyper::StatusCode, headers: &hyper::HeaderMap, mentions_rate_limit: bool, attempt: u32, now: u64) -> Option<u64> {
+    const BACKOFF_BASE_SECS : u64 = 1;
+    const BACKOFF_CAP_SECS : u64 = 64;
+    let is_rate_limited = match status.as_u16() {
+        429 => true,
+        403 => mentions_rate_limit
+            || headers.contains_key("retry-after")
+            || get_header_number(headers, "x-ratelimit-remaining") == Some(0),
+        _ => false
+    };
+    if !is_rate_limited {
+        return None;
+    }
+    if let Some(retry_after) = get_header_number(headers, "retry-after") {
+        return Some(retry_after.max(1));
+    }
+    if get_header_number(headers, "x-ratelimit-remaining") == Some(0) {
+        if let Some(reset) = get_header_number(headers, "x-ratelimit-reset") {
+            return Some(reset.saturating_sub(now).max(1));
+        }
+    }
+    Some((BACKOFF_BASE_SECS << attempt.min(16)).min(BACKOFF_CAP_SECS))
+}
+fn repo_api_url(api_url: &str, project: &str, endpoint: &str) -> String {
+    format!("{}/repos/{}/{}", api_url.trim_end_matches('/'), project, endpoint)
+}
+/// escapes a tag for use in a path, tags may contain characters like '+'
+fn escape_tag(tag: &str) -> String {
+    tag.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b)
+        })
+        .collect::<String>()
+}
+fn commit_url(api_url: &str, project: &str, tag: &str) -> String {
+    repo_api_url(api_url, project, format!("commits/{}", escape_tag(tag)).as_str())
+}
+/// the tarball of a tag built like the `tarball_url` of the tags response,
+/// for tags not listed by it, the full ref keeps a branch of the same name
+/// from being taken instead
+fn tarball_url(api_url: &str, project: &str, tag: &str) -> String {
+    repo_api_url(api_url, project, format!("tarball/refs/tags/{}", escape_tag(tag)).as_str())
+}
+fn first_page_url(api_url: &str, project: &str, source: &TagSource) -> String {
     const PER_PAGE : i32 = 100;
-    let https = HttpsConnector::new();
-    let client = Client::builder().build::<_, hyper::Body>(https);
-    let url = format!("https://api.github.com/repos/{}/tags?per_page={}&page={}", project, PER_PAGE, page);
-    let req = Request::builder()
+    format!("{}?per_page={}", repo_api_url(api_url, project, source.endpoint()), PER_PAGE)
+}
+/// finds the url with the given relation in a RFC 5988 Link header
+fn get_link_url(link_header: &str, rel: &str) -> Option<String> {
+    link_header
+        .split(',')
+        .find_map(|link| {
+            let (url, params) = link.split_once(';')?;
+            let has_rel = params
+                .split(';')
+                .filter_map(|param| param.trim().strip_prefix("rel="))
+                .any(|rels| rels.trim_matches('"').split_whitespace().any(|r| r == rel));
+            has_rel.then(|| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+        })
+}
+fn get_page_number(url: &str) -> Option<u32> {
+    url.split_once('?')?.1
+        .split('&')
+        .find_map(|param| param.strip_prefix("page="))?
+        .parse::<u32>()
+        .ok()
+}
+struct Page {
+    body: String,
+    link: Option<String>
+}
+impl Page {
+    fn next_url(&self) -> Option<String> {
+        get_link_url(self.link.as_ref()?, "next")
+    }
+    fn last_page_number(&self) -> Option<u32> {
+        get_page_number(get_link_url(self.link.as_ref()?, "last")?.as_str())
+    }
+}
+fn gh_request_builder(gh: &GhClient, url: &str) -> hyper::http::request::Builder {
+    Request::builder()
         .uri(url)
         .header("Accept", "application/vnd.github+json")
-        .header("Authorization", format!("Bearer {}", token))
+        .header("Authorization", format!("Bearer {}", gh.token))
         .header("X-GitHub-Api-Version", "2022-11-28")
         .header("User-Agent", "ghdepup/1.0")
-        .body(hyper::Body::empty())
-        .map_err(|e| GetTagsError::HyperHttpError(e))?;
-    let res = client.request(req)
-        .await
-        .map_err(|e| GetTagsError::HyperError(e))?;
-    return match res.status().is_success() {
-        false => {
-            Err(match res.status().as_u16() {
-                401 => GetTagsError::HyperHttpUnauthorizedError(res.status()),
-                _ => GetTagsError::HyperHttpStatusError(res.status())
-            })
-        },
-        true => {
-            let body = res.into_body();
-            let buf = hyper::body::to_bytes(body)
-                .await
-                .map_err(|e| GetTagsError::HyperError(e))?;
-            String::from_utf8(buf.to_vec())
-                .map_err(|e| GetTagsError::FromUtf8Error(e))
+}
+/// sends the request made by `build_request` and retries it as long as it is
+/// rate limited, returning the first successful or not modified response
+async fn send_request<F>(gh: &GhClient, url: &str, build_request: F) -> Result<(hyper::StatusCode, hyper::HeaderMap, hyper::body::Bytes), GetTagsError>
+where F: Fn() -> Result<Request<hyper::Body>, hyper::http::Error>
+{
+    let mut attempt = 0;
+    let mut waited_secs = 0;
+    loop {
+        let req = build_request()
+            .map_err(GetTagsError::HyperHttpError)?;
+        let res = gh.client.request(req)
+            .await
+            .map_err(GetTagsError::HyperError)?;
+        gh.rate_limit.lock().unwrap().record(url, res.headers());
+        let status = res.status();
+        log::debug!("request url={} status={}", url, status.as_u16());
+        let headers = res.headers().clone();
+        let buf = hyper::body::to_bytes(res.into_body())
+            .await
+            .map_err(GetTagsError::HyperError)?;
+        if status.is_success() || status == hyper::StatusCode::NOT_MODIFIED {
+            return Ok((status, headers, buf));
         }
+        let mentions_rate_limit = String::from_utf8_lossy(&buf)
+            .to_lowercase()
+            .contains("rate limit");
+        let wait_secs = match get_rate_limit_wait(status, &headers, mentions_rate_limit, attempt, unix_now()) {
+            None => return Err(match status.as_u16() {
+                401 => GetTagsError::HyperHttpUnauthorizedError(status),
+                _ => GetTagsError::HyperHttpStatusError(status)
+            }),
+            Some(wait_secs) => wait_secs
+        };
+        if attempt >= gh.max_retries || waited_secs + wait_secs > gh.max_retry_wait_secs {
+            return Err(GetTagsError::RateLimitExceeded(status, attempt));
+        }
+        log::warn!("rate limited on {}, retrying in {}s ({}/{})", url, wait_secs, attempt + 1, gh.max_retries);
+        tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+        attempt += 1;
+        waited_secs += wait_secs;
     }
 }
-async fn update_tags_from_gh(dep: &mut Dep, token: &str) -> Result<(), GetTagsError> {
-    let mut tags : Vec<String> = vec![];
-    let mut page = 1;
-    loop {
-        let json = get_repo_tags_json(dep.project.as_str(), token, page)
+async fn get_repo_page(gh: &GhClient, url: &str) -> Result<Page, GetTagsError> {
+    let cached = match &gh.cache {
+        Some(cache) => cache.get(url).await,
+        None => None
+    };
+    let (status, headers, buf) = send_request(gh, url, || {
+        let mut req = gh_request_builder(gh, url);
+        if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_ref()) {
+            req = req.header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = cached.as_ref().and_then(|c| c.last_modified.as_ref()) {
+            req = req.header("If-Modified-Since", last_modified);
+        }
+        req.body(hyper::Body::empty())
+    }).await?;
+    if status == hyper::StatusCode::NOT_MODIFIED {
+        let entry = cached.ok_or(GetTagsError::HyperHttpStatusError(status))?;
+        gh.rate_limit.lock().unwrap().not_modified += 1;
+        return Ok(Page { body: entry.body, link: entry.link });
+    }
+    let get_header = |name| headers.get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let page = Page {
+        body: String::from_utf8(buf.to_vec())
+            .map_err(GetTagsError::FromUtf8Error)?,
+        link: get_header("link")
+    };
+    if let Some(cache) = &gh.cache {
+        let entry = CacheEntry {
+            etag: get_header("etag"),
+            last_modified: get_header("last-modified"),
+            link: page.link.clone(),
+            body: page.body.clone()
+        };
+        if let Err(e) = cache.put(url, &entry).await {
+            log::warn!("unable to write cache entry for {}: {}", url, e);
+        }
+    }
+    Ok(page)
+}
+async fn update_tags_from_gh(dep: &mut Dep, gh: &GhClient) -> Result<(), GetTagsError> {
+    let api_url = dep.api_url.clone().unwrap_or(gh.api_url.clone());
+    let mut next_url = Some(first_page_url(api_url.as_str(), dep.project.as_str(), &dep.source));
+    dep.start_paging();
+    while let Some(url) = next_url {
+        let page = get_repo_page(gh, url.as_str())
             .await?;
-        let mut new_tags = parse_tags_json(json.as_str()).await?;
-        if new_tags.len() > 0 {
-            page = page + 1;
-            tags.append(&mut new_tags);
-        } else {
-            break;
+        let new_tags = match dep.source {
+            TagSource::Tags => parse_tags_json(page.body.as_str()).await?,
+            TagSource::Releases => parse_releases_json(page.body.as_str(), dep.include_prereleases).await?
+        };
+        dep.pages_total = page.last_page_number().or(dep.pages_total);
+        log::debug!("page dep={} number={} of={} tags={}",
+            dep.name,
+            dep.pages_fetched + 1,
+            dep.pages_total.map(|total| total.to_string()).unwrap_or("unknown".to_string()),
+            new_tags.iter().map(|entry| entry.name.as_str()).join(","));
+        next_url = page.next_url();
+        if !dep.add_page_of_tags(new_tags, next_url.is_some()) {
+            next_url = None;
+        }
+    }
+    Ok(())
+}
+async fn update_commit_from_gh(dep: &mut Dep, gh: &GhClient, tag: &str) -> Result<(), GetTagsError> {
+    let api_url = dep.api_url.clone().unwrap_or(gh.api_url.clone());
+    let page = get_repo_page(gh, commit_url(api_url.as_str(), dep.project.as_str(), tag).as_str())
+        .await?;
+    let (commit, date) = parse_commit_json(page.body.as_str())?;
+    dep.add_commit(tag, commit.as_str());
+    dep.add_date(tag, date);
+    Ok(())
+}
+/// fetches the commit dates of the newest candidates until one is old
+/// enough, for deps with a minimum age whose tags came without dates
+async fn update_dates_from_gh(dep: &mut Dep, gh: &GhClient) -> Result<(), GetTagsError> {
+    while let Some(version) = dep.get_undated_candidate() {
+        let tag = match dep.get_tag_of(&version) {
+            Some(tag) => tag.clone(),
+            None => break
+        };
+        update_commit_from_gh(dep, gh, tag.as_str()).await?;
+    }
+    Ok(())
+}
+/// fetches the commit of the best version if it is to be written, but did
+/// not come with the tags as it does not for releases
+async fn update_best_commit_from_gh(dep: &mut Dep, gh: &GhClient) -> Result<(), GetTagsError> {
+    if !dep.get_output_keys().contains(&OutputKey::Commit) {
+        return Ok(());
+    }
+    dep.update_best_version();
+    let tag = dep.best_version.as_ref()
+        .and_then(|best| dep.get_tag_of(best))
+        .filter(|&tag| !dep.tag_commits.contains_key(tag))
+        .cloned();
+    match tag {
+        Some(tag) => update_commit_from_gh(dep, gh, tag.as_str()).await,
+        None => Ok(())
+    }
+}
+/// downloads a tarball and returns its SHA-256, GitHub redirects to the
+/// download host, which does not get the token
+async fn get_tarball_sha256(gh: &GhClient, url: &str) -> Result<String, GetTagsError> {
+    const MAX_REDIRECTS : usize = 5;
+    let mut req = gh_request_builder(gh, url);
+    for redirect in 0..=MAX_REDIRECTS {
+        let request = req.body(hyper::Body::empty()).map_err(GetTagsError::HyperHttpError)?;
+        let hop = request.uri().to_string();
+        let res = gh.client.request(request)
+            .await
+            .map_err(GetTagsError::HyperError)?;
+        if redirect == 0 {
+            gh.rate_limit.lock().unwrap().record(url, res.headers());
+        }
+        let status = res.status();
+        log::debug!("request url={} status={}", hop, status.as_u16());
+        if status.is_redirection() {
+            let location = res.headers().get("location")
+                .and_then(|l| l.to_str().ok())
+                .ok_or(GetTagsError::HyperHttpStatusError(status))?;
+            req = Request::builder()
+                .uri(location)
+                .header("User-Agent", "ghdepup/1.0");
+            continue;
+        }
+        if !status.is_success() {
+            return Err(GetTagsError::HyperHttpStatusError(status));
         }
+        // tarballs can be large, so they are hashed as they come in
+        let mut hasher = Sha256::new();
+        let mut body = res.into_body();
+        while let Some(chunk) = body.data().await {
+            hasher.update(chunk.map_err(GetTagsError::HyperError)?);
+        }
+        return Ok(format!("{:x}", hasher.finalize()));
+    }
+    Err(GetTagsError::TooManyRedirects(url.to_string()))
+}
+/// downloads the tarball of the resolved version if its checksum is to be
+/// written and not known from the previous run
+async fn update_sha256_from_gh(dep: &mut Dep, gh: &GhClient) -> Result<(), GetTagsError> {
+    if let Some(tag) = dep.get_tag_to_checksum() {
+        let api_url = dep.api_url.clone().unwrap_or(gh.api_url.clone());
+        let url = dep.get_tarball_url(api_url.as_str(), tag.as_str());
+        dep.sha256 = Some(get_tarball_sha256(gh, url.as_str()).await?);
+    }
+    Ok(())
+}
+/// downloads the tarball of the current version again and compares it to
+/// the recorded checksum, returning a mismatch as an error message
+async fn verify_sha256_from_gh(dep: &Dep, gh: &GhClient) -> Result<Option<String>, GetTagsError> {
+    let expected = match &dep.current_sha256 {
+        Some(expected) => expected,
+        None => return Ok(None)
+    };
+    let tag = dep.resolved_tag()
+        .ok_or(GetTagsError::NoTagToVerify(dep.name.clone()))?;
+    let api_url = dep.api_url.clone().unwrap_or(gh.api_url.clone());
+    let actual = get_tarball_sha256(gh, dep.get_tarball_url(api_url.as_str(), tag.as_str()).as_str()).await?;
+    if actual == *expected {
+        log::info!("verified {} {}: {}", dep.name, tag, actual);
+        return Ok(None);
+    }
+    Ok(Some(format!("{}: checksum of {} is {}, but {} was recorded", dep.name, tag, actual, expected)))
+}
+fn print_rate_limit(gh: Option<&GhClient>) {
+    if let Some(gh) = gh {
+        log::info!("github api: {}", gh.rate_limit.lock().unwrap());
+    }
+}
+enum ResolveError {
+    FailedDeps(Vec<String>),
+    ChecksumMismatch(Vec<String>)
+}
+impl std::fmt::Debug for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let formatted = match self {
+            Self::FailedDeps(errs) => errs.join("\n"),
+            Self::ChecksumMismatch(mismatches) => mismatches.join("\n")
+        };
+        write!(f, "{}", formatted)
     }
-    dep.available_tags = tags;
+}
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+impl std::error::Error for ResolveError {}
+
+async fn read_snapshot(filename: &str) -> Result<Snapshot, ConfigError> {
+    let content = tokio::fs::read_to_string(filename)
+        .await
+        .or(Err(ConfigError::SnapshotReadError(filename.to_string())))?;
+    Snapshot::from_json(content.as_str())
+        .ok_or(ConfigError::SnapshotReadError(filename.to_string()))
+}
+async fn write_snapshot(deps: &[Dep], filename: &str) -> Result<(), ConfigError> {
+    let mut snapshot = Snapshot::default();
+    deps.iter()
+        .filter(|dep| dep.error.is_none())
+        .for_each(|dep| {
+            snapshot.insert(dep.project.as_str(), dep.available_tags.clone());
+        });
+    tokio::fs::write(filename, snapshot.to_json())
+        .await
+        .or(Err(ConfigError::SnapshotWriteError(filename.to_string())))
+}
+/// picks the best version of each dep that has its tags
+fn resolve_deps(deps: &mut [Dep], settings: &Settings, now: u64) {
+    deps.iter_mut()
+        .filter(|dep| dep.error.is_none())
+        .for_each(|dep| {
+            dep.update_versions_from_tags();
+            dep.apply_min_age(settings.min_age_days, now);
+            dep.apply_output_keys(&settings.output_keys);
+            dep.update_best_version();
+            dep.apply_no_match_policy(&settings.on_no_match);
+            dep.apply_downgrade_protection(settings.allow_downgrade);
+            dep.log_candidates();
+        });
+}
+async fn write_report(deps: &[Dep], filename: &str) -> Result<(), ConfigError> {
+    tokio::fs::write(filename, report::to_json(deps))
+        .await
+        .or(Err(ConfigError::ReportWriteError(filename.to_string())))
+}
+fn update_tags_from_snapshot(dep: &mut Dep, snapshot: &Snapshot) -> Result<(), GetTagsError> {
+    dep.available_tags = snapshot.get(dep.project.as_str())
+        .ok_or(GetTagsError::NotInSnapshot(dep.project.clone()))?
+        .clone();
     Ok(())
 }
-#[cfg(feature="print_debug")]
-async fn print_debug(deps: &Vec<Dep>) {
+fn print_summary(deps: &[Dep]) {
+    let updated = deps.iter()
+        .filter(|dep| dep.error.is_none())
+        .filter(|dep| dep.best_version.is_some() && dep.best_version != dep.current_version)
+        .collect_vec();
+    let refused = deps.iter()
+        .filter(|dep| dep.refused_downgrade.is_some())
+        .collect_vec();
+    let failed = deps.iter()
+        .filter(|dep| dep.error.is_some())
+        .count();
+    log::info!("summary: {} deps, {} updated, {} downgrades refused, {} failed",
+        deps.len(),
+        updated.len(),
+        refused.len(),
+        failed);
+    updated.iter().for_each(|dep| {
+        log::info!("  updated {}: {} -> {}",
+            dep.name,
+            dep.current_version.as_ref().map(|v| v.to_string()).unwrap_or("none".to_string()),
+            dep.best_version.as_ref().map(|v| v.to_string()).unwrap_or_default());
+    });
+    refused.iter().for_each(|dep| {
+        log::info!("  refused downgrade of {}: {} -> {}",
+            dep.name,
+            dep.current_version.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+            dep.refused_downgrade.as_ref().map(|v| v.to_string()).unwrap_or_default());
+    });
+    deps.iter().for_each(|dep| {
+        let ignored = dep.get_candidates_rejected_for(Rejection::Ignored);
+        if !ignored.is_empty() {
+            log::info!("  skipped ignored versions of {}: {}", dep.name, ignored.iter().join(", "));
+        }
+        let waiting = dep.format_waiting_candidates();
+        if !waiting.is_empty() {
+            log::info!("  skipped too new versions of {}: {}", dep.name, waiting);
+        }
+    });
+}
+/// logs the state of each dep, one record per line
+fn log_deps(deps: &[Dep]) {
     deps.iter().for_each(|dep| {
-        println!("{:?}", dep);
+        format!("{:?}", dep).lines().for_each(|line| log::debug!("{}", line));
     });
 }
-#[cfg(not(feature="print_debug"))]
-async fn print_debug(_: &Vec<Dep>) {}
 
-#[cfg(feature="write_outfile")]
-async fn write_outfile(deps: &Vec<Dep>, outfile: &str) {
-    let formatted = deps.iter()
+fn format_outfile(deps: &[Dep]) -> String {
+    deps.iter()
         .map(|d| format!("{}\n", d))
         .reduce(|acc, el| acc + el.as_str())
-        .unwrap_or("".to_string());
-    tokio::fs::write(outfile, formatted.as_bytes())
+        .unwrap_or("".to_string())
+}
+/// a unified diff of the output file as `git diff` would show it, empty if
+/// nothing changes
+fn diff_outfile(current: &str, formatted: &str, outfile: &str) -> String {
+    if current == formatted {
+        return "".to_string();
+    }
+    similar::TextDiff::from_lines(current, formatted)
+        .unified_diff()
+        .header(format!("a/{}", outfile).as_str(), format!("b/{}", outfile).as_str())
+        .to_string()
+}
+/// prints what writing the output file would change instead of writing it
+async fn print_outfile_diff(deps: &[Dep], outfile: &str) {
+    let current = tokio::fs::read_to_string(outfile).await
+        .unwrap_or_default();
+    let diff = diff_outfile(current.as_str(), format_outfile(deps).as_str(), outfile);
+    match diff.is_empty() {
+        true => log::info!("dry run: no changes to {}", outfile),
+        false => print!("{}", diff)
+    }
+}
+async fn write_outfile(deps: &[Dep], outfile: &str) -> Result<(), ConfigError> {
+    tokio::fs::write(outfile, format_outfile(deps).as_bytes())
         .await
-        .expect("fatal: unable to write updated file.")
+        .or(Err(ConfigError::OutputWriteError(outfile.to_string())))
 }
-#[cfg(not(feature="write_outfile"))]
-async fn write_outfile(_: &Vec<Dep>, _: &str) {}
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), Box<dyn std::error::Error>>{
-    let token = env::var("GITHUB_TOKEN")
-        .or(Err(Box::new(ConfigError::GithubTokenMissing()) as Box<dyn std::error::Error>))?;
-    let args = env::args().collect_vec();
-    let (config, outfile) = setup_config(
-        args.iter().map(|a| a.as_str()).collect_vec()
-    ).await
-        .map_err(|e| Box::new(e))?;
+/// prints the deps with a newer version to stdout for check, returning if
+/// any update is at least as big as the threshold
+fn print_outdated(deps: &[Dep], fail_on: &UpdateKind) -> bool {
+    deps.iter()
+        .filter_map(|dep| Some((dep, dep.get_update_kind()?)))
+        .map(|(dep, kind)| {
+            println!("{}: {} -> {} ({})",
+                dep.name,
+                dep.current_version.as_ref().map(|v| v.to_string()).unwrap_or("none".to_string()),
+                dep.best_version.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+                kind.as_str());
+            kind >= *fail_on
+        })
+        // counted instead of any() so that all outdated deps get printed
+        .filter(|&fails| fails)
+        .count() > 0
+}
+
+/// exit code of a run with --keep-going where some deps failed to update
+const EXIT_PARTIAL_SUCCESS : u8 = 3;
+/// exit code of check if updates at least as big as --fail-on are available
+const EXIT_CHECK_OUTDATED : u8 = 1;
+/// exit code of check on any error, so it is not taken as outdated
+const EXIT_CHECK_ERROR : u8 = 2;
+/// exit code of an invalid command line
+const EXIT_USAGE : u8 = 2;
+
+async fn run(settings: Settings, command: Command, files: Vec<&str>) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let (files, outfile) = get_file_names(&command, files, settings.output.as_deref())
+        .map_err(Box::new)?;
+    let config = setup_config(&files, outfile.or(settings.output.as_deref())).await
+        .map_err(Box::new)?;
+    if command == Command::Lint {
+        let problems = lint::lint_config(&config);
+        if !problems.is_empty() {
+            let e : Box<dyn std::error::Error> = Box::new(ConfigError::LintProblems(problems));
+            return Err(e);
+        }
+        log::info!("lint: {} deps, no problems found", config.get_all_deps().len());
+        return Ok(ExitCode::SUCCESS);
+    }
     let mut deps = config.get_all_deps().iter()
         .map(|depname| Dep::from_table(&config, depname))
         .collect_vec();
-    let updates = deps.iter_mut().map(|dep| {
-        update_tags_from_gh(dep, token.as_str())
-    });
-    let updates = join_all(updates).await.into_iter().collect_vec();
-    if !updates.iter().all(|result| result.is_ok()) {
+    let gh = match &settings.offline_snapshot {
+        Some(_) => None,
+        None => {
+            let token = env::var("GITHUB_TOKEN")
+                .or(Err(Box::new(ConfigError::GithubTokenMissing()) as Box<dyn std::error::Error>))?;
+            Some(GhClient::new(&settings, token))
+        }
+    };
+    if command == Command::Verify {
+        let gh = gh.as_ref()
+            .ok_or(Box::new(ConfigError::VerifyOffline()))?;
+        let results = stream::iter(deps.iter().map(|dep| verify_sha256_from_gh(dep, gh)))
+            .buffered(settings.jobs)
+            .collect::<Vec<_>>()
+            .await;
+        print_rate_limit(Some(gh));
+        let mismatches = results.into_iter()
+            .filter_map(|result| result.unwrap_or_else(|e| Some(e.to_string())))
+            .collect_vec();
+        if !mismatches.is_empty() {
+            let e : Box<dyn std::error::Error> = Box::new(ResolveError::ChecksumMismatch(mismatches));
+            return Err(e);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let updates = match &gh {
+        None => {
+            let snapshot = read_snapshot(settings.offline_snapshot.as_deref().unwrap_or_default()).await
+                .map_err(Box::new)?;
+            deps.iter_mut()
+                .map(|dep| update_tags_from_snapshot(dep, &snapshot))
+                .collect_vec()
+        },
+        Some(gh) => {
+            let updates = match settings.backend {
+                Backend::Rest => {
+                    let updates = deps.iter_mut().map(|dep| {
+                        update_tags_from_gh(dep, gh)
+                    });
+                    stream::iter(updates)
+                        .buffered(settings.jobs)
+                        .collect::<Vec<_>>()
+                        .await
+                },
+                Backend::Graphql => graphql::update_tags_from_graphql(&mut deps, gh).await
+            };
+            let now = unix_now();
+            let settings = &settings;
+            let updates = deps.iter_mut()
+                .zip(updates)
+                .map(|(dep, result)| async move {
+                    result?;
+                    dep.update_versions_from_tags();
+                    dep.apply_min_age(settings.min_age_days, now);
+                    dep.apply_output_keys(&settings.output_keys);
+                    update_dates_from_gh(dep, gh).await?;
+                    update_best_commit_from_gh(dep, gh).await
+                });
+            stream::iter(updates)
+                .buffered(settings.jobs)
+                .collect::<Vec<_>>()
+                .await
+        }
+    };
+    deps.iter_mut()
+        .zip(updates.iter())
+        .for_each(|(dep, result)| {
+            if let Err(e) = result {
+                dep.error = Some(e.to_string());
+            }
+        });
+    if !settings.keep_going && !updates.iter().all(|result| result.is_ok()) {
+        print_rate_limit(gh.as_ref());
+        // the report tells which deps failed even if the run is aborted, with
+        // the deps that did not fail resolved as usual
+        if let Some(report_file) = &settings.report_json {
+            resolve_deps(&mut deps, &settings, unix_now());
+            write_report(&deps, report_file.as_str()).await
+                .map_err(Box::new)?;
+        }
         let e : Box<dyn std::error::Error> = Box::new(GetTagsError::MultipleGithubErrors(
             updates.iter()
                 .filter_map(|r|{
@@ -365,13 +2181,106 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
         ));
         return Err(e);
     }
-    deps.iter_mut().for_each(|dep| {
-        dep.update_versions_from_tags();
-        dep.update_best_version();
+    if let Some(record_file) = &settings.record_tags {
+        write_snapshot(&deps, record_file.as_str()).await
+            .map_err(Box::new)?;
+    }
+    resolve_deps(&mut deps, &settings, unix_now());
+    // checking only tells about versions, so there is nothing to download
+    match (&gh, &command) {
+        (_, Command::Check) => {},
+        (None, _) => deps.iter_mut()
+            .filter(|dep| dep.get_tag_to_checksum().is_some())
+            .for_each(|dep| {
+                dep.error = Some("downloading the tarball for its checksum is not possible with --offline".to_string());
+            }),
+        (Some(gh), _) => {
+            let downloads = deps.iter_mut()
+                .map(|dep| async {
+                    if let Err(e) = update_sha256_from_gh(dep, gh).await {
+                        dep.error = Some(format!("failed to download tarball: {}", e));
+                    }
+                });
+            stream::iter(downloads)
+                .buffered(settings.jobs)
+                .collect::<Vec<_>>()
+                .await;
+        }
+    }
+    print_rate_limit(gh.as_ref());
+    let failures = deps.iter()
+        .filter_map(|dep| {
+            dep.error.as_ref().map(|e| format!("{}: {}", dep.name, e))
+        })
+        .collect_vec();
+    if let Some(report_file) = &settings.report_json {
+        write_report(&deps, report_file.as_str()).await
+            .map_err(Box::new)?;
+    }
+    if !settings.keep_going && !failures.is_empty() {
+        let e : Box<dyn std::error::Error> = Box::new(ResolveError::FailedDeps(failures));
+        return Err(e);
+    }
+    failures.iter().for_each(|failure| {
+        log::error!("failed to update {}", failure);
     });
-    print_debug(&deps).await;
-    write_outfile(&deps, outfile.as_str()).await;
-    Ok(())
+    log_deps(&deps);
+    print_summary(&deps);
+    if command == Command::Check {
+        let outdated = print_outdated(&deps, &settings.fail_on);
+        return Ok(match (failures.is_empty(), outdated) {
+            (false, _) => ExitCode::from(EXIT_CHECK_ERROR),
+            (true, true) => ExitCode::from(EXIT_CHECK_OUTDATED),
+            (true, false) => ExitCode::SUCCESS
+        });
+    }
+    match (&command, outfile) {
+        (Command::Show, _) => print!("{}", format_outfile(&deps)),
+        (_, Some(outfile)) if settings.dry_run => print_outfile_diff(&deps, outfile).await,
+        (_, Some(outfile)) => write_outfile(&deps, outfile).await?,
+        _ => {}
+    }
+    if !failures.is_empty() {
+        return Ok(ExitCode::from(EXIT_PARTIAL_SUCCESS));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = env::args().collect_vec();
+    let (settings, command, files) = match parse_options(
+        args.iter().map(|a| a.as_str()).collect_vec(),
+        Settings::from_env()
+    ) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        },
+        Command::Version => {
+            println!("ghdepup {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        },
+        _ => {}
+    }
+    logger::init(settings.log_level);
+    let check = command == Command::Check;
+    match run(settings, command, files).await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            match check {
+                true => ExitCode::from(EXIT_CHECK_ERROR),
+                false => ExitCode::FAILURE
+            }
+        }
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -405,8 +2314,51 @@ mod tests {
         }
         ]";
         let expected = "1.0.0, 1.2.3";
-        let actual = parse_tags_json(json).await.ok().expect("this should parse");
-        assert_eq!(join(actual.iter(), ", "), expected);
+        let actual = parse_tags_json(json).await.expect("this should parse");
+        assert_eq!(join(actual.iter().map(|t| t.name.as_str()), ", "), expected);
+        assert_eq!(actual[0].commit.as_deref(), Some("5d305789a86bc9a3d8a352522b219396ad4f3930"));
+        assert_eq!(actual[0].tarball_url.as_deref(), Some("https://api.github.com/repos/bjoernmichaelsen/core/tarball/refs/tags/suse-4.0-1"));
+    }
+    #[tokio::test]
+    async fn test_get_link_url() {
+        let link = "<https://api.github.com/repositories/1/tags?per_page=100&page=2>; rel=\"next\", \
+            <https://api.github.com/repositories/1/tags?per_page=100&page=42>; rel=\"last\"";
+        assert_eq!(
+            get_link_url(link, "next"),
+            Some("https://api.github.com/repositories/1/tags?per_page=100&page=2".to_string()));
+        assert_eq!(
+            get_link_url(link, "last"),
+            Some("https://api.github.com/repositories/1/tags?per_page=100&page=42".to_string()));
+        assert_eq!(get_link_url(link, "prev"), None);
+        assert_eq!(get_link_url("", "next"), None);
+        assert_eq!(get_page_number("https://api.github.com/repositories/1/tags?per_page=100&page=42"), Some(42));
+        assert_eq!(get_page_number("https://api.github.com/repositories/1/tags?per_page=100"), None);
+    }
+    #[tokio::test]
+    async fn test_parse_releases_json() {
+        let json = "
+        [
+        {
+            \"tag_name\": \"v2.0.0\",
+            \"draft\": true,
+            \"prerelease\": false
+        },
+        {
+            \"tag_name\": \"v1.1.0-rc.1\",
+            \"draft\": false,
+            \"prerelease\": true
+        },
+        {
+            \"tag_name\": \"v1.0.0\",
+            \"draft\": false,
+            \"prerelease\": false,
+            \"published_at\": \"2024-02-29T12:30:15Z\"
+        }
+        ]";
+        let actual = parse_releases_json(json, false).await.expect("this should parse");
+        assert_eq!(actual, vec![TagEntry { name: "v1.0.0".to_string(), commit: None, date: Some(1709209815), tarball_url: None }]);
+        let actual = parse_releases_json(json, true).await.expect("this should parse");
+        assert_eq!(join(actual.iter().map(|t| t.name.as_str()), ", "), "v1.1.0-rc.1, v1.0.0");
     }
     static CONFIG_CONTENT : &str = "
 # this config should be kept parsable by POSIX sh, make, ini and toml
@@ -431,7 +2383,7 @@ HYPER_TLS_GH_VERSION=\"0.5.0\"
         let hyper = Dep::from_table(&config, "hyper");
         assert_eq!(hyper.name, "hyper");
         assert_eq!(hyper.project, "hyperium/hyper");
-        assert_eq!(hyper.tag_prefix, "v");
+        assert_eq!(hyper.tag_pattern.description, "the prefix \"v\"");
         assert_eq!(hyper.version_req, VersionReq::parse(">=0.14, <1").ok());
         assert_eq!(hyper.available_versions.len(), 0);
         assert_eq!(hyper.available_tags.len(), 0);
@@ -441,6 +2393,232 @@ HYPER_TLS_GH_VERSION=\"0.5.0\"
         assert_eq!(hyper.best_version, None)
     }
     #[tokio::test]
+    async fn test_parse_options() {
+        let settings = Settings::from_env();
+        let (settings, command, positional) = parse_options(
+            vec!["ghdepup", "--api-url", "http://localhost:8080/api/v3/", "deps.toml", "--max-retries", "2", "--cache-dir", "/tmp/cache", "versions.toml"],
+            settings
+        ).expect("should parse");
+        assert_eq!(command, Command::Update);
+        assert_eq!(join(positional, ", "), "deps.toml, versions.toml");
+        assert_eq!(settings.max_retries, 2);
+        assert_eq!(settings.cache_dir, Some(PathBuf::from("/tmp/cache")));
+        assert_eq!(
+            repo_api_url(settings.api_url.as_str(), "hyperium/hyper", "tags"),
+            "http://localhost:8080/api/v3/repos/hyperium/hyper/tags");
+        assert!(parse_options(vec!["ghdepup", "--backend", "soap"], Settings::from_env()).is_err());
+        assert!(parse_options(vec!["ghdepup", "--jobs", "0"], Settings::from_env()).is_err());
+        assert!(parse_options(vec!["ghdepup", "--fail-on", "minor"], Settings::from_env())
+            .is_ok_and(|(settings, _, _)| settings.fail_on == UpdateKind::Minor));
+        assert!(parse_options(vec!["ghdepup", "--api-url"], settings).is_err());
+    }
+    #[tokio::test]
+    async fn test_parse_command() {
+        let (settings, command, positional) = parse_options(
+            vec!["ghdepup", "check", "deps.toml", "-o", "versions.toml"],
+            Settings::from_env()
+        ).expect("should parse");
+        assert_eq!(command, Command::Check);
+        assert_eq!(settings.output.as_deref(), Some("versions.toml"));
+        assert_eq!(
+            get_file_names(&command, positional, settings.output.as_deref()).expect("should have files"),
+            (vec!["deps.toml"], None));
+        let (settings, command, positional) = parse_options(
+            vec!["ghdepup", "deps.toml", "--output", "versions.toml"],
+            Settings::from_env()
+        ).expect("should parse");
+        assert_eq!(
+            get_file_names(&command, positional, settings.output.as_deref()).expect("should have files"),
+            (vec!["deps.toml"], Some("versions.toml")));
+        assert_eq!(
+            get_file_names(&Command::Update, vec!["deps.toml", "versions.toml"], None).expect("should have files"),
+            (vec!["deps.toml", "versions.toml"], Some("versions.toml")));
+        assert!(get_file_names(&Command::Update, vec!["deps.toml"], None)
+            .is_err_and(|e| e.to_string().contains("--output")));
+        assert!(get_file_names(&Command::Lint, vec![], None).is_err());
+        let (_, command, _) = parse_options(vec!["ghdepup", "show", "--help"], Settings::from_env())
+            .expect("should parse");
+        assert_eq!(command, Command::Help);
+        let (_, command, _) = parse_options(vec!["ghdepup", "-V"], Settings::from_env())
+            .expect("should parse");
+        assert_eq!(command, Command::Version);
+        let (settings, _, _) = parse_options(vec!["ghdepup", "-v", "deps.toml"], Settings::from_env())
+            .expect("should parse");
+        assert_eq!(settings.log_level, LevelFilter::Debug);
+        let (settings, _, _) = parse_options(vec!["ghdepup", "-vv", "--quiet"], Settings::from_env())
+            .expect("should parse");
+        assert_eq!(settings.log_level, LevelFilter::Error);
+        let (settings, _, _) = parse_options(vec!["ghdepup", "-v", "--verbose", "-v"], Settings::from_env())
+            .expect("should parse");
+        assert_eq!(settings.log_level, LevelFilter::Trace);
+        ["-x", "-vvv", "-qv", "--verbosity", "--dry-run=yes"].iter().for_each(|&option| {
+            assert!(parse_options(vec!["ghdepup", option, "deps.toml"], Settings::from_env()).is_err());
+        });
+        let (settings, _, positional) = parse_options(
+            vec!["ghdepup", "--output=out.toml", "--jobs=4", "--api-url=http://localhost:8080/?a=b", "deps.toml"],
+            Settings::from_env()
+        ).expect("should parse");
+        assert_eq!(settings.output.as_deref(), Some("out.toml"));
+        assert_eq!(settings.jobs, 4);
+        assert_eq!(settings.api_url, "http://localhost:8080/?a=b");
+        assert_eq!(positional, vec!["deps.toml"]);
+        assert!(parse_options(vec!["ghdepup", "--jobs=", "deps.toml"], Settings::from_env()).is_err());
+    }
+    #[tokio::test]
+    async fn test_get_rate_limit_wait() {
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(get_rate_limit_wait(hyper::StatusCode::FORBIDDEN, &headers, false, 0, 1000), None);
+        assert_eq!(get_rate_limit_wait(hyper::StatusCode::NOT_FOUND, &headers, true, 0, 1000), None);
+        assert_eq!(get_rate_limit_wait(hyper::StatusCode::FORBIDDEN, &headers, true, 3, 1000), Some(8));
+        assert_eq!(get_rate_limit_wait(hyper::StatusCode::TOO_MANY_REQUESTS, &headers, false, 10, 1000), Some(64));
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1042".parse().unwrap());
+        assert_eq!(get_rate_limit_wait(hyper::StatusCode::FORBIDDEN, &headers, false, 0, 1000), Some(42));
+        headers.insert("retry-after", "7".parse().unwrap());
+        assert_eq!(get_rate_limit_wait(hyper::StatusCode::FORBIDDEN, &headers, false, 0, 1000), Some(7));
+    }
+    #[tokio::test]
+    async fn test_rate_limit_stats() {
+        let mut stats = RateLimitStats::default();
+        assert_eq!(stats.to_string(), "0 requests (0 not modified), rate limit budget used: unknown");
+        let headers = |remaining: &str, resource: &str| {
+            let mut headers = hyper::HeaderMap::new();
+            headers.insert("x-ratelimit-limit", "5000".parse().unwrap());
+            headers.insert("x-ratelimit-remaining", remaining.parse().unwrap());
+            headers.insert("x-ratelimit-resource", resource.parse().unwrap());
+            headers
+        };
+        // out of order as with several jobs
+        stats.record("https://api.github.com/repos/o/a/tags", &headers("4990", "core"));
+        stats.record("https://api.github.com/repos/o/b/tags", &headers("4988", "core"));
+        stats.record("https://api.github.com/repos/o/c/tags", &headers("4989", "core"));
+        stats.record("https://api.github.com/graphql", &headers("4999", "graphql"));
+        stats.record("https://ghes.example.com/api/v3/repos/o/d/tags", &headers("120", "core"));
+        stats.record("https://ghes.example.com/api/v3/repos/o/d/tags", &hyper::HeaderMap::new());
+        assert_eq!(stats.to_string(), "6 requests (0 not modified), rate limit budget used: \
+            api.github.com core 3 (4988/5000 remaining), \
+            api.github.com graphql 1 (4999/5000 remaining), \
+            ghes.example.com core 1 (120/5000 remaining)");
+    }
+    #[tokio::test]
+    async fn test_failed_dep_keeps_current_version() {
+        let config = toml::from_str::<Table>(CONFIG_CONTENT)
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        hyper.best_version = Version::parse("0.14.29").ok();
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.29\"");
+        hyper.error = Some("unexpected http status: 404 Not Found".to_string());
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.26\"");
+    }
+    #[tokio::test]
+    async fn test_no_match() {
+        let config = toml::from_str::<Table>(CONFIG_CONTENT)
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        assert_eq!(hyper.get_no_match(), Some(NoMatch::NoTags));
+        hyper.available_tags = vec!["hyper-1.0.0".to_string(), "1.0.0".to_string()];
+        assert_eq!(hyper.get_no_match(), Some(NoMatch::NoTagMatchesPattern(2, "the prefix \"v\"".to_string())));
+        hyper.available_tags = vec!["vfoo".to_string()];
+        hyper.update_versions_from_tags();
+        assert_eq!(hyper.get_no_match(), Some(NoMatch::NoValidVersion(1)));
+        hyper.available_tags = vec!["v1.0.0".to_string(), "vfoo".to_string()];
+        hyper.update_versions_from_tags();
+        hyper.update_best_version();
+        assert_eq!(hyper.get_no_match(), Some(NoMatch::NoneEligible(1, "1 not meeting the requirement \">=0.14, <1\"".to_string())));
+        hyper.apply_no_match_policy(&NoMatchPolicy::Keep);
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.26\"");
+        hyper.apply_no_match_policy(&NoMatchPolicy::Fail);
+        assert!(hyper.error.is_some());
+        hyper.available_tags.push("v0.14.29".to_string());
+        hyper.update_versions_from_tags();
+        hyper.update_best_version();
+        assert_eq!(hyper.get_no_match(), None);
+        // versions dropped for other reasons than the requirement
+        hyper.ignore_versions = IgnoredVersion::parse_list("0.14.29");
+        hyper.available_tags.push("v0.14.30".to_string());
+        hyper.update_versions_from_tags();
+        hyper.available_dates.insert(Version::new(0, 14, 30), 2000);
+        hyper.min_age_cutoff = Some(1000);
+        hyper.update_best_version();
+        assert_eq!(
+            hyper.get_no_match().map(|no_match| no_match.to_string()),
+            Some("none of the 3 versions is eligible: 1 not meeting the requirement \">=0.14, <1\", 1 ignored, 1 too new".to_string()));
+    }
+    #[tokio::test]
+    async fn test_downgrade_protection() {
+        let config = toml::from_str::<Table>(CONFIG_CONTENT)
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        hyper.best_version = Version::parse("0.14.20").ok();
+        hyper.apply_downgrade_protection(true);
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.20\"");
+        hyper.apply_downgrade_protection(false);
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.26\"");
+        assert_eq!(hyper.refused_downgrade, Version::parse("0.14.20").ok());
+        let config = toml::from_str::<Table>(format!("{}\nHYPER_GH_ALLOW_DOWNGRADE=\"true\"", CONFIG_CONTENT).as_str())
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        hyper.best_version = Version::parse("0.14.20").ok();
+        hyper.apply_downgrade_protection(false);
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.20\"");
+    }
+    #[tokio::test]
+    async fn test_min_age() {
+        let config = toml::from_str::<Table>(format!("{}\nHYPER_GH_MIN_AGE_DAYS=\"7\"", CONFIG_CONTENT).as_str())
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        let now = date::parse_timestamp("2024-03-10T00:00:00Z").unwrap();
+        hyper.add_page_of_tags(vec![
+            TagEntry { name: "v0.14.27".to_string(), commit: None, date: date::parse_timestamp("2024-02-01T00:00:00Z"), tarball_url: None },
+            TagEntry { name: "v0.14.28".to_string(), commit: None, date: date::parse_timestamp("2024-03-01T00:00:00Z"), tarball_url: None },
+            TagEntry { name: "v0.14.29".to_string(), commit: None, date: None, tarball_url: None },
+            TagEntry { name: "v0.14.30".to_string(), commit: None, date: date::parse_timestamp("2024-03-05T00:00:00Z"), tarball_url: None }
+        ], false);
+        hyper.update_versions_from_tags();
+        hyper.apply_min_age(0, now);
+        assert_eq!(hyper.get_undated_candidate(), Version::parse("0.14.29").ok());
+        assert_eq!(hyper.get_tag_of(&Version::new(0, 14, 29)).map(String::as_str), Some("v0.14.29"));
+        hyper.add_date("v0.14.29", date::parse_timestamp("2024-03-04T00:00:00Z").unwrap());
+        assert_eq!(hyper.get_undated_candidate(), None);
+        hyper.update_best_version();
+        assert_eq!(hyper.best_version, Version::parse("0.14.28").ok());
+        assert_eq!(hyper.format_waiting_candidates(), "0.14.29 (eligible on 2024-03-11), 0.14.30 (eligible on 2024-03-12)");
+        hyper.apply_min_age(30, now);
+        hyper.update_best_version();
+        assert_eq!(hyper.best_version, Version::parse("0.14.28").ok());
+        let mut hyper_tls = Dep::from_table(&config, "hyper_tls");
+        hyper_tls.apply_min_age(0, now);
+        assert_eq!(hyper_tls.min_age_cutoff, None);
+    }
+    #[tokio::test]
+    async fn test_paging_newest_first() {
+        let config = toml::from_str::<Table>(format!("{}\nHYPER_GH_PAGING=\"newest-first\"", CONFIG_CONTENT).as_str())
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        let page = |tags: &[&str]| tags.iter()
+            .map(|tag| TagEntry { name: tag.to_string(), commit: None, date: None, tarball_url: None })
+            .collect_vec();
+        hyper.start_paging();
+        hyper.pages_total = Some(5);
+        // nothing to compare with on the first page
+        assert!(hyper.add_page_of_tags(page(&["v1.0.0", "v0.14.28"]), true));
+        assert!(!hyper.stopped_early);
+        assert!(hyper.add_page_of_tags(page(&["v0.14.30", "v0.14.27"]), true));
+        assert!(!hyper.stopped_early);
+        assert!(!hyper.add_page_of_tags(page(&["v0.14.26", "v0.14.25"]), true));
+        assert!(hyper.stopped_early);
+        assert_eq!(hyper.format_paging(), "3 (2 requests saved by stopping early)");
+        hyper.update_versions_from_tags();
+        hyper.update_best_version();
+        assert_eq!(hyper.best_version, Version::parse("0.14.30").ok());
+        // the last page is not stopping early
+        hyper.start_paging();
+        assert!(hyper.add_page_of_tags(page(&["v0.14.28"]), true));
+        assert!(!hyper.add_page_of_tags(page(&["v0.14.27"]), false));
+        assert!(!hyper.stopped_early);
+        assert_eq!(hyper.format_paging(), "2");
+    }
+    #[tokio::test]
     async fn test_get_versions_from_tags() {
         let tags: Vec<&str> =vec![
                 "foo111",
@@ -448,21 +2626,266 @@ HYPER_TLS_GH_VERSION=\"0.5.0\"
                 "toml-v1.2.3",
                 "v2.3.4",
                 "toml-v3.4.5"];
-        let versions = Dep::get_versions_from_tags(tags, "toml-v");
+        let versions = Dep::get_versions_from_tags(tags, &TagPattern::from_prefix("toml-v", ""));
         assert_eq!(
             join(versions, ", "),
             "1.2.3, 3.4.5");
     }
     #[tokio::test]
+    async fn test_lenient_version_parse() {
+        assert_eq!(VersionParse::Strict.parse("1.2"), None);
+        assert_eq!(VersionParse::Lenient.parse("1.2"), Version::parse("1.2.0").ok());
+        assert_eq!(VersionParse::Lenient.parse("2024.01"), Version::parse("2024.1.0").ok());
+        assert_eq!(VersionParse::Lenient.parse("3"), Version::parse("3.0.0").ok());
+        assert_eq!(VersionParse::Lenient.parse("1.02-rc.1"), Version::parse("1.2.0-rc.1").ok());
+        assert_eq!(VersionParse::Lenient.parse("1.2.3.4"), None);
+        assert_eq!(VersionParse::Lenient.parse("1..2"), None);
+        assert_eq!(VersionParse::Lenient.parse("foo"), None);
+        let config = toml::from_str::<Table>("
+LIBFOO_GH_PROJECT=\"example/libfoo\"
+LIBFOO_GH_TAG_PREFIX=\"v\"
+LIBFOO_GH_VERSION_PARSE=\"lenient\"
+LIBFOO_GH_PRERELEASE=\"never\"
+LIBFOO_GH_VERSION=\"1.1\"
+LIBFOO_GH_TAG=\"v1.1\"
+        ").expect("should parse");
+        let mut libfoo = Dep::from_table(&config, "libfoo");
+        assert_eq!(libfoo.current_version, Version::parse("1.1.0").ok());
+        assert_eq!(libfoo.to_string(), "LIBFOO_GH_VERSION=\"1.1.0\"\nLIBFOO_GH_TAG=\"v1.1\"");
+        libfoo.available_tags = vec!["v1.1".to_string(), "v1.2".to_string(), "v1.10-beta".to_string()];
+        libfoo.update_versions_from_tags();
+        libfoo.update_best_version();
+        assert_eq!(libfoo.to_string(), "LIBFOO_GH_VERSION=\"1.2.0\"\nLIBFOO_GH_TAG=\"v1.2\"");
+    }
+    #[tokio::test]
+    async fn test_output_keys() {
+        assert_eq!(OutputKey::parse_list("tag, commit"), Ok(vec![OutputKey::Tag, OutputKey::Commit]));
+        assert_eq!(OutputKey::parse_list("tag,sha"), Err(()));
+        let config = toml::from_str::<Table>(format!("{}
+HYPER_GH_OUTPUT_KEYS=\"tag,commit\"
+HYPER_GH_TAG=\"v0.14.26\"
+HYPER_GH_COMMIT=\"abc\"", CONFIG_CONTENT).as_str())
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.26\"\nHYPER_GH_TAG=\"v0.14.26\"\nHYPER_GH_COMMIT=\"abc\"");
+        hyper.add_page_of_tags(vec![
+            TagEntry { name: "v0.14.26".to_string(), commit: Some("abc".to_string()), date: None, tarball_url: None },
+            TagEntry { name: "v0.14.27".to_string(), commit: Some("def".to_string()), date: None, tarball_url: None }
+        ], false);
+        hyper.update_versions_from_tags();
+        hyper.update_best_version();
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.27\"\nHYPER_GH_TAG=\"v0.14.27\"\nHYPER_GH_COMMIT=\"def\"");
+        hyper.error = Some("failed".to_string());
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.26\"\nHYPER_GH_TAG=\"v0.14.26\"\nHYPER_GH_COMMIT=\"abc\"");
+        let mut hyper_tls = Dep::from_table(&config, "hyper_tls");
+        hyper_tls.apply_output_keys(&[OutputKey::Commit]);
+        assert_eq!(hyper_tls.to_string(), "HYPER_TLS_GH_VERSION=\"0.5.0\"\nHYPER_TLS_GH_COMMIT=\"\"");
+    }
+    #[tokio::test]
+    async fn test_diff_outfile() {
+        let config = toml::from_str::<Table>(CONFIG_CONTENT)
+            .expect("should parse");
+        let mut deps = vec![Dep::from_table(&config, "hyper"), Dep::from_table(&config, "hyper_tls")];
+        let current = "HYPER_GH_VERSION=\"0.14.26\"\nHYPER_TLS_GH_VERSION=\"0.5.0\"\n";
+        assert_eq!(diff_outfile(current, format_outfile(&deps).as_str(), "ghversions.toml"), "");
+        deps[0].best_version = Version::parse("0.14.29").ok();
+        assert_eq!(
+            diff_outfile(current, format_outfile(&deps).as_str(), "ghversions.toml"),
+            "--- a/ghversions.toml\n+++ b/ghversions.toml\n@@ -1,2 +1,2 @@\n-HYPER_GH_VERSION=\"0.14.26\"\n+HYPER_GH_VERSION=\"0.14.29\"\n HYPER_TLS_GH_VERSION=\"0.5.0\"\n");
+        assert!(write_outfile(&deps, "/nonexistent/ghversions.toml").await.is_err());
+    }
+    #[tokio::test]
+    async fn test_update_kind() {
+        let config = toml::from_str::<Table>(CONFIG_CONTENT)
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        assert_eq!(hyper.get_update_kind(), None);
+        hyper.best_version = Version::parse("0.14.26").ok();
+        assert_eq!(hyper.get_update_kind(), None);
+        hyper.best_version = Version::parse("0.14.27").ok();
+        assert_eq!(hyper.get_update_kind(), Some(UpdateKind::Patch));
+        hyper.best_version = Version::parse("0.15.0").ok();
+        assert_eq!(hyper.get_update_kind(), Some(UpdateKind::Minor));
+        hyper.best_version = Version::parse("1.0.0").ok();
+        assert_eq!(hyper.get_update_kind(), Some(UpdateKind::Major));
+        assert!(UpdateKind::Major >= UpdateKind::Minor);
+        assert!(UpdateKind::Patch < UpdateKind::Minor);
+        assert!(print_outdated(&[hyper], &UpdateKind::Major));
+        hyper = Dep::from_table(&config, "hyper");
+        hyper.best_version = Version::parse("0.14.27").ok();
+        assert!(!print_outdated(&[hyper], &UpdateKind::Minor));
+        hyper = Dep::from_table(&config, "hyper");
+        hyper.best_version = Version::parse("1.0.0").ok();
+        hyper.error = Some("unexpected http status: 404 Not Found".to_string());
+        assert_eq!(hyper.get_update_kind(), None);
+    }
+    #[tokio::test]
+    async fn test_sha256_output() {
+        let config = toml::from_str::<Table>(format!("{}
+HYPER_GH_OUTPUT_KEYS=\"sha256\"
+HYPER_GH_SHA256=\"0123\"", CONFIG_CONTENT).as_str())
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        assert_eq!(hyper.get_tag_to_checksum(), None);
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.26\"\nHYPER_GH_SHA256=\"0123\"");
+        assert_eq!(hyper.resolved_tag(), Some("v0.14.26".to_string()));
+        hyper.available_tags = vec!["v0.14.27".to_string()];
+        hyper.update_versions_from_tags();
+        hyper.update_best_version();
+        assert_eq!(hyper.get_tag_to_checksum(), Some("v0.14.27".to_string()));
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.27\"\nHYPER_GH_SHA256=\"\"");
+        hyper.sha256 = Some("4567".to_string());
+        assert_eq!(hyper.to_string(), "HYPER_GH_VERSION=\"0.14.27\"\nHYPER_GH_SHA256=\"4567\"");
+        assert_eq!(
+            hyper.get_tarball_url("https://api.github.com", "v1.0.0+build"),
+            "https://api.github.com/repos/hyperium/hyper/tarball/refs/tags/v1.0.0%2Bbuild");
+        hyper.add_page_of_tags(vec![TagEntry {
+            name: "v0.14.27".to_string(),
+            commit: None,
+            date: None,
+            tarball_url: Some("https://ghes.example.com/api/v3/repos/hyperium/hyper/tarball/refs/tags/v0.14.27".to_string())
+        }], false);
+        assert_eq!(
+            hyper.get_tarball_url("https://api.github.com", "v0.14.27"),
+            "https://ghes.example.com/api/v3/repos/hyperium/hyper/tarball/refs/tags/v0.14.27");
+    }
+    #[tokio::test]
+    async fn test_get_versions_from_tags_with_pattern() {
+        let tags: Vec<&str> =vec![
+                "release-1_2_3",
+                "prerelease-2_0_0",
+                "v1.3.0-final",
+                "pkg@1.4.0",
+                "other@1.5.0"];
+        let pattern = TagPattern::from_regex("release-(?P<version>.*)", "_").expect("should compile");
+        assert_eq!(join(Dep::get_versions_from_tags(tags.clone(), &pattern), ", "), "1.2.3");
+        let pattern = TagPattern::from_regex("v(?P<version>.*)-final", "").expect("should compile");
+        assert_eq!(join(Dep::get_versions_from_tags(tags.clone(), &pattern), ", "), "1.3.0");
+        let pattern = TagPattern::from_regex("pkg@(?<version>[0-9.]+)", "").expect("should compile");
+        assert_eq!(join(Dep::get_versions_from_tags(tags.clone(), &pattern), ", "), "1.4.0");
+        assert!(TagPattern::from_regex("pkg@(.*)", "").is_err());
+        assert!(TagPattern::from_regex("pkg@(?P<version>.*", "").is_err());
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+HYPER_GH_TAG_PATTERN=\"v(?P<version>\\\\d+\\\\.\\\\d+\\\\.\\\\d+)\"
+HYPER_TLS_GH_PROJECT=\"hyperium/hyper-tls\"
+HYPER_TLS_GH_TAG_PATTERN=\"v.*\"
+        ").expect("should parse");
+        assert_eq!(Dep::from_table(&config, "hyper").error, None);
+        assert_eq!(
+            Dep::from_table(&config, "hyper_tls").error,
+            Some("tag pattern \"v.*\" has no group named version".to_string()));
+    }
+    #[tokio::test]
     async fn test_get_best_version() {
-        let versions =vec![
+        let versions =[
                 "1.2.3",
                 "3.4.0",
                 "3.4.5",
                 "4.5.6"].iter().map(|&s| {Version::parse(s).unwrap()}).collect_vec();
         let version_req = VersionReq::from_str(">=3, <4").unwrap();
         assert_eq!(
-            Dep::get_best_version(versions.iter().collect_vec(), Some(&version_req)),
+            Dep::get_best_version(versions.iter().collect_vec(), &VersionConstraints {
+                version_req: Some(&version_req),
+                ..Default::default()
+            }),
             Version::parse("3.4.5").ok());
     }
+    #[tokio::test]
+    async fn test_get_best_version_with_prereleases() {
+        let mut versions =[
+                "0.9.0",
+                "1.0.0-rc.1",
+                "1.0.0-rc.2",
+                "2.0.0-alpha.1"].iter().map(|&s| {Version::parse(s).unwrap()}).collect_vec();
+        let version_req = VersionReq::from_str(">=0.9, <2").unwrap();
+        let best = |versions: &Vec<Version>, policy: PrereleasePolicy, current: &str| {
+            let current = Version::parse(current).unwrap();
+            Dep::get_best_version(versions.iter().collect_vec(), &VersionConstraints {
+                version_req: Some(&version_req),
+                allow_prereleases: policy.allows_prereleases(Some(&current)),
+                ..Default::default()
+            }).map(|v| v.to_string())
+        };
+        assert_eq!(best(&versions, PrereleasePolicy::Never, "0.9.0"), Some("0.9.0".to_string()));
+        let never = VersionConstraints { version_req: Some(&version_req), ..Default::default() };
+        assert_eq!(never.get_rejection(&versions[1]), Some(Rejection::Prerelease));
+        assert_eq!(never.get_rejection(&versions[3]), Some(Rejection::VersionReq));
+        assert_eq!(VersionConstraints::default().get_rejection(&versions[1]), Some(Rejection::Prerelease));
+        assert_eq!(best(&versions, PrereleasePolicy::Allow, "0.9.0"), Some("1.0.0-rc.2".to_string()));
+        assert_eq!(best(&versions, PrereleasePolicy::OnlyIfCurrentIsPrerelease, "0.9.0"), Some("0.9.0".to_string()));
+        assert_eq!(best(&versions, PrereleasePolicy::OnlyIfCurrentIsPrerelease, "1.0.0-rc.1"), Some("1.0.0-rc.2".to_string()));
+        versions.push(Version::parse("1.0.0").unwrap());
+        assert_eq!(best(&versions, PrereleasePolicy::OnlyIfCurrentIsPrerelease, "1.0.0-rc.2"), Some("1.0.0".to_string()));
+        assert_eq!(
+            Dep::get_best_version(versions.iter().collect_vec(), &VersionConstraints::default()),
+            Version::parse("1.0.0").ok());
+    }
+    #[tokio::test]
+    async fn test_get_best_version_with_ignore_list() {
+        let versions =[
+                "1.2.3",
+                "1.3.0",
+                "1.4.0-rc.1",
+                "1.4.0",
+                "1.4.1",
+                "1.5.0"].iter().map(|&s| {Version::parse(s).unwrap()}).collect_vec();
+        let ignore_versions = IgnoredVersion::parse_list("1.5.0, 1.4.*, not a version,");
+        assert_eq!(ignore_versions.len(), 2);
+        let constraints = VersionConstraints {
+            allow_prereleases: true,
+            ignore_versions: &ignore_versions,
+            ..Default::default()
+        };
+        assert_eq!(
+            Dep::get_best_version(versions.iter().collect_vec(), &constraints),
+            Version::parse("1.3.0").ok());
+        assert_eq!(constraints.get_rejection(&versions[2]), Some(Rejection::Ignored));
+        assert_eq!(constraints.get_rejection(&versions[1]), None);
+        // the comparators of a range are not separate entries
+        let ignore_versions = IgnoredVersion::parse_list(">=1.4, <1.5, 1.2.3, 1.3.0");
+        assert_eq!(ignore_versions.len(), 3);
+        let constraints = VersionConstraints {
+            ignore_versions: &ignore_versions,
+            ..Default::default()
+        };
+        assert_eq!(
+            Dep::get_best_version(versions.iter().collect_vec(), &constraints),
+            Version::parse("1.5.0").ok());
+        assert_eq!(constraints.get_rejection(&versions[3]), Some(Rejection::Ignored));
+        assert_eq!(constraints.get_rejection(&versions[0]), Some(Rejection::Ignored));
+        // several ranges and exact versions mixed
+        let ignore_versions = IgnoredVersion::parse_list("1.5.0, 1.3.*, >=1.4, <1.4.1, ~1.4.1");
+        assert_eq!(ignore_versions.len(), 4);
+        let constraints = VersionConstraints {
+            ignore_versions: &ignore_versions,
+            ..Default::default()
+        };
+        assert_eq!(
+            Dep::get_best_version(versions.iter().collect_vec(), &constraints),
+            Version::parse("1.2.3").ok());
+        assert_eq!(IgnoredVersion::split_list("<1, >=2"), vec!["<1", ">=2"]);
+    }
+    #[tokio::test]
+    async fn test_get_best_version_with_update_policy() {
+        let versions =[
+                "1.2.3",
+                "1.2.9",
+                "1.3.0",
+                "2.0.0"].iter().map(|&s| {Version::parse(s).unwrap()}).collect_vec();
+        let current = Version::parse("1.2.3").unwrap();
+        let version_req = VersionReq::from_str("<1.3").unwrap();
+        let best_with_policy = |policy: UpdatePolicy, version_req: Option<&VersionReq>| {
+            Dep::get_best_version(versions.iter().collect_vec(), &VersionConstraints {
+                version_req,
+                update_policy: Some(&policy),
+                current_version: Some(&current),
+                ..Default::default()
+            }).map(|v| v.to_string())
+        };
+        assert_eq!(best_with_policy(UpdatePolicy::Patch, None), Some("1.2.9".to_string()));
+        assert_eq!(best_with_policy(UpdatePolicy::Minor, None), Some("1.3.0".to_string()));
+        assert_eq!(best_with_policy(UpdatePolicy::Major, None), Some("2.0.0".to_string()));
+        assert_eq!(best_with_policy(UpdatePolicy::Major, Some(&version_req)), Some("1.2.9".to_string()));
+    }
 }
@@ -0,0 +1,101 @@
+use itertools::Itertools;
+use serde_json::json;
+use serde_json::Value;
+
+use super::Dep;
+
+/// the state of a dep after a run, with the data the debug output has as
+/// comments
+fn dep_to_json(dep: &Dep) -> Value {
+    let to_string = |v: &semver::Version| v.to_string();
+    json!({
+        "name": dep.name,
+        "project": dep.project,
+        "tag_prefix": dep.tag_pattern.prefix,
+        "requirement": dep.version_req.as_ref().map(|vr| vr.to_string()),
+        "update_policy": dep.update_policy.as_ref().map(|policy| policy.as_str()),
+        "current": dep.current_version.as_ref().map(to_string),
+        "best": dep.best_version.as_ref().map(to_string),
+        // what the requirement or the update policy keeps a dep from
+        "newest": dep.available_versions.iter().max().map(to_string),
+        "available": dep.available_versions.iter().sorted().map(to_string).collect_vec(),
+        "update": dep.get_update_kind().map(|kind| kind.as_str()).unwrap_or("none"),
+        "errors": dep.error.iter().collect_vec()
+    })
+}
+/// a report of a run for dashboards and other tools
+pub fn to_json(deps: &[Dep]) -> String {
+    let report = json!({
+        "deps": deps.iter().map(dep_to_json).collect_vec()
+    });
+    let mut json = serde_json::to_string_pretty(&report)
+        .unwrap_or_default();
+    json.push('\n');
+    json
+}
+#[cfg(test)]
+mod tests {
+    use toml::Table;
+
+    use super::*;
+    use super::super::Settings;
+    use super::super::resolve_deps;
+    #[tokio::test]
+    async fn test_report_json() {
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+HYPER_GH_TAG_PREFIX=\"v\"
+HYPER_GH_VERSION_REQ=\">=0.14, <1\"
+HYPER_GH_VERSION=\"0.14.26\"
+TOKIO_GH_PROJECT=\"tokio-rs/tokio\"
+TOKIO_GH_TAG_PATTERN=\"tokio-(?P<version>.*)\"")
+            .expect("should parse");
+        let mut hyper = Dep::from_table(&config, "hyper");
+        hyper.available_tags = vec!["v1.0.0".to_string(), "v0.14.27".to_string(), "v0.14.26".to_string()];
+        hyper.update_versions_from_tags();
+        hyper.update_best_version();
+        let mut tokio = Dep::from_table(&config, "tokio");
+        tokio.error = Some("unexpected http status: 404 Not Found".to_string());
+        let report : Value = serde_json::from_str(to_json(&[hyper, tokio]).as_str())
+            .expect("should parse");
+        assert_eq!(report["deps"][0], json!({
+            "name": "hyper",
+            "project": "hyperium/hyper",
+            "tag_prefix": "v",
+            "requirement": ">=0.14, <1",
+            "update_policy": null,
+            "current": "0.14.26",
+            "best": "0.14.27",
+            "newest": "1.0.0",
+            "available": ["0.14.26", "0.14.27", "1.0.0"],
+            "update": "patch",
+            "errors": []
+        }));
+        assert_eq!(report["deps"][1]["tag_prefix"], Value::Null);
+        assert_eq!(report["deps"][1]["update"], "none");
+        assert_eq!(report["deps"][1]["errors"], json!(["unexpected http status: 404 Not Found"]));
+    }
+    #[tokio::test]
+    async fn test_report_json_of_aborted_run() {
+        let config = toml::from_str::<Table>("
+HYPER_GH_PROJECT=\"hyperium/hyper\"
+HYPER_GH_TAG_PREFIX=\"v\"
+HYPER_GH_VERSION_REQ=\">=0.14, <1\"
+HYPER_GH_VERSION=\"0.14.26\"
+GONE_GH_PROJECT=\"o/gone\"")
+            .expect("should parse");
+        // as left by fetching the tags when one project could not be found
+        let mut hyper = Dep::from_table(&config, "hyper");
+        hyper.available_tags = vec!["v0.14.27".to_string(), "v0.14.26".to_string()];
+        let mut gone = Dep::from_table(&config, "gone");
+        gone.error = Some("no tags for o/gone in the offline snapshot".to_string());
+        let mut deps = vec![hyper, gone];
+        resolve_deps(&mut deps, &Settings::from_env(), 0);
+        let report : Value = serde_json::from_str(to_json(&deps).as_str())
+            .expect("should parse");
+        assert_eq!(report["deps"][0]["best"], "0.14.27");
+        assert_eq!(report["deps"][0]["update"], "patch");
+        assert_eq!(report["deps"][1]["best"], Value::Null);
+        assert_eq!(report["deps"][1]["errors"], json!(["no tags for o/gone in the offline snapshot"]));
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+use serde_json::Value;
+
+/// the tags of each project as seen by a previous run, so that versions can
+/// be resolved again without access to GitHub
+#[derive(Debug, Default, PartialEq)]
+pub struct Snapshot {
+    tags: BTreeMap<String, Vec<String>>
+}
+impl Snapshot {
+    pub fn from_json(json: &str) -> Option<Self> {
+        let v : Value = serde_json::from_str(json).ok()?;
+        let tags = v.as_object()?
+            .iter()
+            .map(|(project, tags)| {
+                let tags = tags.as_array()?
+                    .iter()
+                    .map(|t| t.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<String>>>()?;
+                Some((project.clone(), tags))
+            })
+            .collect::<Option<BTreeMap<String, Vec<String>>>>()?;
+        Some(Self { tags })
+    }
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(&self.tags)
+            .unwrap_or_default();
+        json.push('\n');
+        json
+    }
+    pub fn get(&self, project: &str) -> Option<&Vec<String>> {
+        self.tags.get(project)
+    }
+    pub fn insert(&mut self, project: &str, tags: Vec<String>) {
+        self.tags.insert(project.to_string(), tags);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[tokio::test]
+    async fn test_snapshot_roundtrip() {
+        let mut snapshot = Snapshot::default();
+        snapshot.insert("hyperium/hyper", vec!["v1.0.0".to_string(), "v0.14.29".to_string()]);
+        snapshot.insert("dtolnay/semver", vec![]);
+        let json = snapshot.to_json();
+        let parsed = Snapshot::from_json(json.as_str()).expect("should parse");
+        assert_eq!(parsed, snapshot);
+        assert_eq!(parsed.get("hyperium/hyper").map(|t| t.join(", ")), Some("v1.0.0, v0.14.29".to_string()));
+        assert_eq!(parsed.get("rust-lang/rust"), None);
+        assert_eq!(Snapshot::from_json("{\"hyperium/hyper\": [1]}"), None);
+    }
+}